@@ -420,7 +420,6 @@
             systemd.tmpfiles.rules = [
               "d ${cfg.dataDir} 0750 ${cfg.user} ${cfg.group} -"
               "d ${cfg.dataDir}/server 0750 ${cfg.user} ${cfg.group} -"
-              "d ${cfg.dataDir}/frontend 0750 ${cfg.user} ${cfg.group} -"
               "d ${cfg.dataDir}/plugins 0750 ${cfg.user} ${cfg.group} -"
               "d ${cfg.dataDir}/plugin_web 0750 ${cfg.user} ${cfg.group} -"
             ] ++ map (p:
@@ -436,17 +435,21 @@
                 description = "Timeline main server";
                 wantedBy = [ "multi-user.target" ];
                 after = [ "network.target" ] ++ map (p: "timeline-plugin-${p.name}.service") cfg.plugins;
+                environment = {
+                  TIMELINE_CONFIG = "${cfg.dataDir}/server/config.toml";
+                  TIMELINE_FRONTEND_DIR = "${cfg.frontendDist}";
+                };
                 serviceConfig = {
                   Type = "simple";
                   User = cfg.user;
                   Group = cfg.group;
                   WorkingDirectory = "${cfg.dataDir}/server";
                   ExecStartPre = [
-                    "${pkgs.coreutils}/bin/mkdir -p ${cfg.dataDir}/server ${cfg.dataDir}/frontend"
-                    "${pkgs.coreutils}/bin/ln -sfn ${cfg.frontendDist} ${cfg.dataDir}/frontend/dist"
+                    "${pkgs.coreutils}/bin/mkdir -p ${cfg.dataDir}/server"
                     (renderCmd serverConfigToml "${cfg.dataDir}/server/config.toml")
+                    "${cfg.package}/bin/server check-config"
                   ];
-                  ExecStart = "${cfg.package}/bin/server";
+                  ExecStart = "${cfg.package}/bin/server serve";
                   Restart = "always";
                 } // lib.optionalAttrs (cfg.environmentFile != null) {
                   EnvironmentFile = cfg.environmentFile;
@@ -0,0 +1,98 @@
+//! `/errors`: the server's error log — what plugins reported and which of
+//! their calls failed — most recent first.
+
+use chrono::{DateTime, Local, Utc};
+use leptos::prelude::*;
+
+use types::api::{APIError, ErrorReport};
+
+use crate::api::api_request;
+use crate::plugin_manager::PluginManager;
+use crate::wrappers::{Login, StyledView, TitleBar};
+
+#[component]
+pub fn Errors() -> impl IntoView {
+    let last_auth = RwSignal::new(Utc::now().timestamp_millis());
+    let reports = LocalResource::new(move || {
+        let _ = last_auth.get();
+        async { api_request::<Vec<ErrorReport>, _>("/errors", &()).await }
+    });
+
+    let clear =
+        Action::new_local(|_: &()| async { api_request::<(), _>("/errors/clear", &()).await });
+    Effect::new(move |_| {
+        if clear.value().get().is_some() {
+            reports.refetch();
+        }
+    });
+
+    let plugin_manager_action = Action::new_local(|_: &()| async { PluginManager::load().await });
+    Effect::new(move |_| {
+        if plugin_manager_action.value().get_untracked().is_none() {
+            plugin_manager_action.dispatch(());
+        }
+    });
+    let plugin_manager =
+        Signal::derive(move || plugin_manager_action.value().get().unwrap_or_default());
+
+    view! {
+        <StyledView>
+            <TitleBar subtitle=Signal::derive(|| Some("Errors".to_string())) />
+            {move || match reports.get() {
+                None => view! { <div class="infoWrapper">Loading...</div> }.into_any(),
+                Some(Err(APIError::AuthenticationError)) => view! {
+                    <Login update_authentication=last_auth.write_only() />
+                }.into_any(),
+                Some(Err(e)) => view! {
+                    <div class="errorWrapper">{format!("Unable to load errors: {}", e)}</div>
+                }.into_any(),
+                Some(Ok(reports)) if reports.is_empty() => view! {
+                    <div class="infoWrapper">No errors recorded</div>
+                }.into_any(),
+                Some(Ok(reports)) => {
+                    let manager = plugin_manager.get();
+                    view! {
+                        <button class="clearErrors" on:click=move |_| { clear.dispatch(()); }>
+                            "Clear"
+                        </button>
+                        <div class="errorList">
+                            {reports
+                                .into_iter()
+                                .map(|report| view! {
+                                    <div class="errorRow">
+                                        <div class="errorRowHeader">
+                                            <b>{manager.display_name(&report.plugin)}</b>
+                                            <span>{occurrences(&report)}</span>
+                                        </div>
+                                        <div class="errorMessage">{report.message.clone()}</div>
+                                        {report.request_id.clone().map(|id| view! {
+                                            <div class="errorRequestId">{format!("Request {}", id)}</div>
+                                        })}
+                                    </div>
+                                })
+                                .collect_view()}
+                        </div>
+                    }.into_any()
+                }
+            }}
+        </StyledView>
+    }
+}
+
+fn occurrences(report: &ErrorReport) -> String {
+    let time = |t: DateTime<Utc>| {
+        DateTime::<Local>::from(t)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    };
+    if report.count == 1 {
+        time(report.last_seen)
+    } else {
+        format!(
+            "{}× · {} – {}",
+            report.count,
+            time(report.first_seen),
+            time(report.last_seen)
+        )
+    }
+}
@@ -1,38 +1,130 @@
 //! Event fetching + filtering to a time range, then hand off to events_display.
+//! Events are fetched a page at a time, more as the list is scrolled; events
+//! pushed over `/api/live` are merged in as they arrive.
 
 use std::collections::HashMap;
 
 use leptos::prelude::*;
 
-use types::api::CompressedEvent;
+use types::api::{CompressedEvent, EventOrder, EventsRequest, EventsResponse, LiveEvents};
 use types::timing::TimeRange;
 
 use crate::api::api_request;
-use crate::events_display::EventsViewer;
+use crate::events_display::{failed_plugins, EventsViewer, LoadMore};
+use crate::live;
 use crate::plugin_manager::PluginManager;
 
 type EventMap = HashMap<String, Vec<CompressedEvent>>;
 
+/// Most events per plugin in one `/api/events` page.
+const PAGE_SIZE: u32 = 200;
+
+fn page_request(range: TimeRange, cursor: Option<String>) -> EventsRequest {
+    EventsRequest {
+        range,
+        limit: Some(PAGE_SIZE),
+        order: EventOrder::Asc,
+        cursor,
+    }
+}
+
 #[component]
 pub fn EventManager(
     #[prop(into)] available_range: Signal<TimeRange>,
     #[prop(into)] current_range: Signal<TimeRange>,
     #[prop(into)] plugin_manager: Signal<PluginManager>,
+    /// App to show first instead of the first with events.
+    #[prop(default = None)]
+    initial_app: Option<String>,
 ) -> impl IntoView {
     let available_events = LocalResource::new(move || {
-        let range = available_range.get();
+        let request = page_request(available_range.get(), None);
         async move {
             leptos::logging::log!("reloading events");
-            api_request::<EventMap, _>("/events", &range).await
+            api_request::<EventsResponse, _>("/events", &request).await
         }
     });
 
-    let filtered = Memo::new(move |_| -> Option<Result<EventMap, String>> {
+    // Pages after the first, and the cursor for the one after those.
+    let more_pages: RwSignal<Vec<EventsResponse>> = RwSignal::new(Vec::new());
+    let next: RwSignal<Option<String>> = RwSignal::new(None);
+    Effect::new(move |_| {
+        if let Some(Ok(first)) = available_events.get() {
+            more_pages.set(Vec::new());
+            next.set(first.next);
+        }
+    });
+    let load_page = Action::new_local(|(range, cursor): &(TimeRange, String)| {
+        let range = range.clone();
+        let request = page_request(range.clone(), Some(cursor.clone()));
+        async move {
+            (
+                range,
+                api_request::<EventsResponse, _>("/events", &request).await,
+            )
+        }
+    });
+    Effect::new(move |_| {
+        let Some((range, result)) = load_page.value().get() else {
+            return;
+        };
+        if range != available_range.get_untracked() {
+            return;
+        }
+        match result {
+            Ok(page) => {
+                next.set(page.next.clone());
+                more_pages.update(|p| p.push(page));
+            }
+            Err(e) => {
+                leptos::logging::warn!("loading more events failed: {}", e);
+                next.set(None);
+            }
+        }
+    });
+    provide_context(LoadMore {
+        available: Signal::derive(move || next.with(Option::is_some)),
+        pending: load_page.pending().into(),
+        load: Callback::new(move |_| {
+            if let Some(cursor) = next.get_untracked() {
+                load_page.dispatch((available_range.get_untracked(), cursor));
+            }
+        }),
+    });
+
+    // Live messages received since the last fetch; a new fetch includes them.
+    let live_events: RwSignal<Vec<LiveEvents>> = RwSignal::new(Vec::new());
+    Effect::new(move |_| {
+        let _ = available_range.get();
+        live_events.set(Vec::new());
+    });
+    live::subscribe(
+        move |msg| live_events.update(|l| l.push(msg)),
+        move || {
+            live_events.set(Vec::new());
+            available_events.refetch();
+        },
+    );
+
+    let filtered = Memo::new(move |_| -> Option<Result<EventsResponse, String>> {
         let inner = available_events.get()?;
         match inner.clone() {
-            Ok(all) => {
+            Ok(mut all) => {
+                more_pages.with(|pages| {
+                    for page in pages {
+                        for (plugin, events) in &page.events {
+                            all.events
+                                .entry(plugin.clone())
+                                .or_default()
+                                .extend(events.iter().cloned());
+                        }
+                        all.plugins.extend(page.plugins.clone());
+                    }
+                });
+                live_events.with(|l| live::merge(&mut all.events, l, &available_range.get()));
                 let range = current_range.get();
-                let out: EventMap = all
+                let events: EventMap = all
+                    .events
                     .into_iter()
                     .map(|(plugin, events)| {
                         let kept: Vec<CompressedEvent> = events
@@ -43,24 +135,46 @@ pub fn EventManager(
                     })
                     .filter(|(_, e)| !e.is_empty())
                     .collect();
-                Some(Ok(out))
+                Some(Ok(EventsResponse {
+                    events,
+                    plugins: all.plugins,
+                    next: None,
+                }))
             }
             Err(e) => Some(Err(e.to_string())),
         }
     });
 
+    // Only loading/error transitions rebuild the viewer, so new events
+    // (live or refetched) don't reset the selected app.
+    let loaded = Memo::new(move |_| filtered.with(Option::is_some));
+    let error = Memo::new(move |_| match filtered.get() {
+        Some(Err(e)) => Some(e),
+        _ => None,
+    });
+    let events = Signal::derive(move || match filtered.get() {
+        Some(Ok(res)) => res.events,
+        _ => EventMap::new(),
+    });
+    let failed = Signal::derive(move || match filtered.get() {
+        Some(Ok(res)) => failed_plugins(&res.plugins),
+        _ => Vec::new(),
+    });
+
     view! {
-        {move || match filtered.get() {
-            Some(Ok(map)) => view! {
+        {move || match (loaded.get(), error.get()) {
+            (false, _) => view! { <div class="infoWrapper">Loading...</div> }.into_any(),
+            (true, Some(e)) => view! {
+                <div class="errorWrapper">{format!("Error loading events: {}", e)}</div>
+            }.into_any(),
+            (true, None) => view! {
                 <EventsViewer
-                    events=Signal::derive(move || map.clone())
+                    events=events
+                    failed=failed
                     plugin_manager=plugin_manager
+                    initial_app=initial_app.clone()
                 />
             }.into_any(),
-            Some(Err(e)) => view! {
-                <div class="errorWrapper">{format!("Error loading events: {}", e)}</div>
-            }.into_any(),
-            None => view! { <div class="infoWrapper">Loading...</div> }.into_any(),
         }}
     }
 }
@@ -4,22 +4,50 @@
 use std::collections::HashMap;
 
 use leptos::prelude::*;
+use send_wrapper::SendWrapper;
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
 
-use types::api::CompressedEvent;
+use types::api::{CompressedEvent, PluginOutcome, PluginStatus};
 
 use crate::plugin_manager::PluginManager;
 
 #[derive(Clone, Copy)]
 pub struct DisplayWithDay(pub bool);
 
+/// Provided by a parent that fetches events page by page: event lists ask
+/// for the next page once scrolled to the end.
+#[derive(Clone, Copy)]
+pub struct LoadMore {
+    pub available: Signal<bool>,
+    pub pending: Signal<bool>,
+    pub load: Callback<()>,
+}
+
 type EventMap = HashMap<String, Vec<CompressedEvent>>;
 
+/// Plugins from an `/api/events` response that didn't answer, sorted by name.
+pub fn failed_plugins(status: &HashMap<String, PluginStatus>) -> Vec<(String, PluginOutcome)> {
+    let mut failed: Vec<_> = status
+        .iter()
+        .filter(|(_, s)| !s.outcome.is_ok())
+        .map(|(name, s)| (name.clone(), s.outcome.clone()))
+        .collect();
+    failed.sort_by(|a, b| a.0.cmp(&b.0));
+    failed
+}
+
 #[component]
 pub fn EventsViewer(
     #[prop(into)] events: Signal<EventMap>,
+    /// Plugins whose events couldn't be loaded; shown as a warning.
+    #[prop(into)]
+    failed: Signal<Vec<(String, PluginOutcome)>>,
     #[prop(into)] plugin_manager: Signal<PluginManager>,
+    /// App selected at first; otherwise the first one with events.
+    #[prop(default = None)]
+    initial_app: Option<String>,
 ) -> impl IntoView {
     let available_plugins = Memo::new(move |_| {
         let mut plugins: Vec<String> = events.with(|e| e.keys().cloned().collect());
@@ -27,7 +55,7 @@ pub fn EventsViewer(
         plugins
     });
 
-    let current_app: RwSignal<Option<String>> = RwSignal::new(None);
+    let current_app: RwSignal<Option<String>> = RwSignal::new(initial_app);
 
     // Auto-select first app if none chosen.
     Effect::new(move |_| {
@@ -48,12 +76,29 @@ pub fn EventsViewer(
     let plugins_signal = Signal::derive(move || available_plugins.get());
     let events_signal = Signal::derive(move || current_events.get());
     view! {
+        <Show when=move || failed.with(|f| !f.is_empty())>
+            <div class="pluginWarning">
+                "Some plugins couldn't load their events: "
+                {move || {
+                    let manager = plugin_manager.get();
+                    failed
+                        .get()
+                        .into_iter()
+                        .map(|(name, outcome)| format!("{} ({})", manager.display_name(&name), outcome))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }}
+            </div>
+        </Show>
         <AppSelect plugins=plugins_signal current_app=current_app manager=plugin_manager />
         {move || match current_app.get() {
             Some(name) => view! {
                 <EventsDisplay plugin_name=name events=events_signal manager=plugin_manager />
             }.into_any(),
-            None => view! { <div class="infoWrapper">No App Selected</div> }.into_any(),
+            None => view! {
+                <div class="infoWrapper">No App Selected</div>
+                {use_context::<LoadMore>().map(|more| view! { <LoadMoreSentinel more=more /> })}
+            }.into_any(),
         }}
     }
 }
@@ -74,8 +119,14 @@ fn AppSelect(
                     let name_for_click = name.clone();
                     let name_for_indicator = name.clone();
                     let name_for_color = name.clone();
+                    let name_for_down = name.clone();
+                    let name_for_title = name.clone();
                     view! {
-                        <div class="iconWrap">
+                        <div
+                            class="iconWrap"
+                            class:down=move || manager.with(|m| m.is_down(&name_for_down))
+                            title=move || manager.with(|m| m.down_reason(&name_for_title))
+                        >
                             <img
                                 src=icon_url
                                 class="appIcon"
@@ -135,6 +186,47 @@ fn EventsDisplay(
                     }
                 }}
             />
+            {use_context::<LoadMore>().map(|more| view! { <LoadMoreSentinel more=more /> })}
+        </div>
+    }
+}
+
+/// Loads the next page whenever it's scrolled into view, and again after
+/// each page while it stays in view.
+#[component]
+fn LoadMoreSentinel(more: LoadMore) -> impl IntoView {
+    let node: NodeRef<leptos::html::Div> = NodeRef::new();
+    let visible = RwSignal::new(false);
+    Effect::new(move |_| {
+        let Some(el) = node.get() else { return };
+        let callback = Closure::<dyn Fn(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            let entry = entries
+                .iter()
+                .last()
+                .and_then(|e| e.dyn_into::<web_sys::IntersectionObserverEntry>().ok());
+            if let Some(entry) = entry {
+                visible.set(entry.is_intersecting());
+            }
+        });
+        let Ok(observer) = web_sys::IntersectionObserver::new(callback.as_ref().unchecked_ref())
+        else {
+            return;
+        };
+        observer.observe(&el);
+        let handle = SendWrapper::new((observer, callback));
+        on_cleanup(move || {
+            let (observer, _callback) = handle.take();
+            observer.disconnect();
+        });
+    });
+    Effect::new(move |_| {
+        if visible.get() && more.available.get() && !more.pending.get() {
+            more.load.run(());
+        }
+    });
+    view! {
+        <div class="loadMore" node_ref=node>
+            {move || more.pending.get().then_some("Loading more...")}
         </div>
     }
 }
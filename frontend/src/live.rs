@@ -0,0 +1,97 @@
+//! Subscription to the server's `/api/live` event stream, and merging what
+//! arrives on it into already fetched events.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gloo_events::EventListener;
+use leptos::prelude::*;
+use send_wrapper::SendWrapper;
+use wasm_bindgen::JsCast;
+
+use types::api::{CompressedEvent, LiveEvents};
+use types::timing::TimeRange;
+
+use crate::api::relative_url;
+
+/// Call `on_events` for every message on `/api/live` until the current
+/// reactive owner is cleaned up. `on_resync` is called when messages may
+/// have been missed (the server dropped some, or the connection was
+/// re-established); callers should refetch.
+pub fn subscribe(on_events: impl Fn(LiveEvents) + 'static, on_resync: impl Fn() + 'static) {
+    let init = web_sys::EventSourceInit::new();
+    init.set_with_credentials(true);
+    let source = match web_sys::EventSource::new_with_event_source_init_dict(
+        &relative_url("/api/live"),
+        &init,
+    ) {
+        Ok(s) => s,
+        Err(e) => {
+            leptos::logging::warn!("live updates unavailable: {:?}", e);
+            return;
+        }
+    };
+
+    let on_resync = Rc::new(on_resync);
+    let events = EventListener::new(&source, "events", move |e| {
+        let Some(data) = e
+            .dyn_ref::<web_sys::MessageEvent>()
+            .and_then(|m| m.data().as_string())
+        else {
+            return;
+        };
+        match serde_json::from_str::<LiveEvents>(&data) {
+            Ok(live) => on_events(live),
+            Err(e) => leptos::logging::warn!("bad live message: {}", e),
+        }
+    });
+    let resync = {
+        let on_resync = on_resync.clone();
+        EventListener::new(&source, "resync", move |_| on_resync())
+    };
+    // The browser reconnects on its own; anything sent in between is lost.
+    let opened_before = Cell::new(false);
+    let reopen = EventListener::new(&source, "open", move |_| {
+        if opened_before.replace(true) {
+            on_resync();
+        }
+    });
+
+    let handle = SendWrapper::new((source, events, resync, reopen));
+    on_cleanup(move || {
+        let (source, ..) = handle.take();
+        source.close();
+    });
+}
+
+/// Apply `live` messages to `events`, keeping only events overlapping
+/// `range`. An incoming event replaces one with the same time and title
+/// (an update); anything else is added.
+pub fn merge(
+    events: &mut HashMap<String, Vec<CompressedEvent>>,
+    live: &[LiveEvents],
+    range: &TimeRange,
+) {
+    for msg in live {
+        let incoming: Vec<_> = msg
+            .events
+            .iter()
+            .filter(|e| range.overlap_timing(&e.time))
+            .collect();
+        if incoming.is_empty() {
+            continue;
+        }
+        let list = events.entry(msg.plugin.clone()).or_default();
+        for event in incoming {
+            match list
+                .iter_mut()
+                .find(|e| e.time == event.time && e.title == event.title)
+            {
+                Some(existing) => *existing = event.clone(),
+                None => list.push(event.clone()),
+            }
+        }
+        list.sort_by(|a, b| a.time.cmp(&b.time));
+    }
+}
@@ -1,28 +1,33 @@
 mod api;
+mod errors;
 mod event_manager;
 mod events_display;
+mod live;
 mod plugin_manager;
+mod search;
 mod style;
 mod timeline_view;
 mod wrappers;
 
 use std::str::FromStr;
 
-use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeDelta, Timelike, Utc};
 use leptos::prelude::*;
 use leptos_router::components::{Route, Router, Routes};
-use leptos_router::hooks::{use_navigate, use_params};
+use leptos_router::hooks::{use_navigate, use_params, use_query_map};
 use leptos_router::params::Params;
 use leptos_router::{path, NavigateOptions};
-use types::api::{APIError, CompressedEvent};
+use types::api::{APIError, CompressedEvent, EventsResponse, LiveEvents};
 use types::timing::TimeRange;
 
 use crate::api::{api_request, TimelineHostname};
+use crate::errors::Errors;
 use crate::event_manager::EventManager;
-use crate::events_display::{DisplayWithDay, EventsViewer};
+use crate::events_display::{failed_plugins, DisplayWithDay, EventsViewer};
 use crate::plugin_manager::PluginManager;
+use crate::search::Search;
 use crate::timeline_view::TimelineBar;
-use crate::wrappers::{Login, StyledView, TitleBar};
+use crate::wrappers::{Login, Logout, StyledView, TitleBar};
 
 fn main() {
     console_error_panic_hook::set_once();
@@ -43,6 +48,8 @@ fn MainView() -> impl IntoView {
                 <Route path=path!("/timeline") view=Timeline />
                 <Route path=path!("/event/latest/exclude/:exclude") view=LatestEvent />
                 <Route path=path!("/event/latest") view=LatestEvent />
+                <Route path=path!("/search") view=Search />
+                <Route path=path!("/errors") view=Errors />
                 <Route path=path!("/") view=Redirect />
             </Routes>
         </Router>
@@ -115,11 +122,26 @@ fn Timeline() -> impl IntoView {
         end: DateTime::from_timestamp_millis(0).unwrap_or_default(),
     });
 
+    // A full timestamp in the URL (e.g. from search) opens the hour around
+    // it rather than the start of the day.
+    let focus = Memo::new(move |_| {
+        let p = params.get().ok()?;
+        let t = DateTime::<Utc>::from_str(p.date.as_deref()?).ok()?;
+        let hour = DateTime::<Local>::from(t)
+            .with_nanosecond(0)?
+            .with_second(0)?
+            .with_minute(0)?;
+        Some(DateTime::<Utc>::from(hour))
+    });
+    let query = use_query_map();
+    let initial_app = Memo::new(move |_| query.with(|q| q.get("plugin")));
+
     Effect::new(move |_| {
         if let Ok(r) = day_range.get() {
+            let start = focus.get().filter(|f| r.includes(f)).unwrap_or(r.start);
             current_range.set(TimeRange {
-                start: r.start,
-                end: r.start + TimeDelta::try_hours(1).unwrap_or_default(),
+                start,
+                end: start + TimeDelta::try_hours(1).unwrap_or_default(),
             });
         }
     });
@@ -162,7 +184,7 @@ fn Timeline() -> impl IntoView {
                         />
                         <div
                             class="dateSelectWrapper"
-                            style:max-height=move || if date_select_expanded.get() { "100px" } else { "0px" }
+                            style:max-height=move || if date_select_expanded.get() { "300px" } else { "0px" }
                         >
                             <input
                                 class="dateSelect"
@@ -174,6 +196,9 @@ fn Timeline() -> impl IntoView {
                                 }
                                 style:color-scheme="dark"
                             />
+                            <a class="searchLink" href="/search">Search all events</a>
+                            <a class="searchLink" href="/errors">Errors</a>
+                            <Logout update_authentication=last_auth.write_only() />
                         </div>
                         {move || match authentication.get() {
                             None => view! { <div class="infoWrapper">Loading...</div> }.into_any(),
@@ -190,11 +215,13 @@ fn Timeline() -> impl IntoView {
                                     <TimelineBar
                                         range=Signal::derive(move || day_bar.clone())
                                         on_range_pick=on_range_pick
+                                        plugin_manager=plugin_manager
                                     />
                                     <EventManager
                                         available_range=Signal::derive(move || day_mgr.clone())
                                         current_range=Signal::derive(move || current_range.get())
                                         plugin_manager=plugin_manager
+                                        initial_app=initial_app.get_untracked()
                                     />
                                 }.into_any()
                             }
@@ -264,11 +291,18 @@ fn LatestEvent() -> impl IntoView {
 
     let events_resource = LocalResource::new(move || {
         let r = range.get();
-        async move {
-            api_request::<std::collections::HashMap<String, Vec<CompressedEvent>>, _>("/events", &r).await
-        }
+        async move { api_request::<EventsResponse, _>("/events", &r).await }
     });
 
+    let live_events: RwSignal<Vec<LiveEvents>> = RwSignal::new(Vec::new());
+    live::subscribe(
+        move |msg| live_events.update(|l| l.push(msg)),
+        move || {
+            live_events.set(Vec::new());
+            events_resource.refetch();
+        },
+    );
+
     let params = use_params::<LatestParams>();
     let exclude: Memo<Vec<String>> = Memo::new(move |_| match params.get() {
         Ok(p) => p
@@ -294,15 +328,27 @@ fn LatestEvent() -> impl IntoView {
     view! {
         <StyledView>
             {move || match events_resource.get() {
-                Some(Ok(events)) => {
+                Some(Ok(mut res)) => {
+                    // Anything announced since the fetch, up to now.
+                    let since = TimeRange {
+                        start: range.get().start,
+                        end: Utc::now(),
+                    };
+                    live_events.with(|l| live::merge(&mut res.events, l, &since));
                     let excluded = exclude.get();
-                    let events_map: std::collections::HashMap<String, Vec<CompressedEvent>> = events
+                    let failed: Vec<_> = failed_plugins(&res.plugins)
+                        .into_iter()
+                        .filter(|(plugin, _)| !excluded.contains(plugin))
+                        .collect();
+                    let events_map: std::collections::HashMap<String, Vec<CompressedEvent>> = res
+                        .events
                         .into_iter()
                         .filter(|(plugin, _)| !excluded.contains(plugin))
                         .collect();
                     view! {
                         <EventsViewer
                             events=Signal::derive(move || events_map.clone())
+                            failed=Signal::derive(move || failed.clone())
                             plugin_manager=plugin_manager
                         />
                     }.into_any()
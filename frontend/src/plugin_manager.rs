@@ -11,7 +11,7 @@ use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use types::api::CompressedEvent;
+use types::api::{Capability, CompressedEvent, HealthState, PluginHealth};
 
 use crate::api::api_request;
 use crate::style::Style;
@@ -28,6 +28,10 @@ pub struct PluginManifest {
     /// Unset → no UI; events render title only.
     #[serde(default)]
     pub web_entry: Option<String>,
+    /// Optional protocol parts the plugin implements; unset for plugins
+    /// built before they were declared.
+    #[serde(default)]
+    pub capabilities: Option<Vec<Capability>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -50,17 +54,20 @@ pub struct PluginContext {
 #[derive(Debug, Clone, Default)]
 pub struct PluginManager {
     plugins: HashMap<String, PluginManifest>,
+    /// From `/api/status`; empty if that request failed.
+    health: HashMap<String, PluginHealth>,
 }
 
 impl PluginManager {
     pub async fn load() -> Self {
-        match api_request::<Vec<PluginManifest>, ()>("/plugins", &()).await {
-            Ok(v) => {
-                let plugins = v.into_iter().map(|m| (m.name.clone(), m)).collect();
-                PluginManager { plugins }
-            }
-            Err(_) => PluginManager::default(),
-        }
+        let plugins = match api_request::<Vec<PluginManifest>, ()>("/plugins", &()).await {
+            Ok(v) => v.into_iter().map(|m| (m.name.clone(), m)).collect(),
+            Err(_) => HashMap::new(),
+        };
+        let health = api_request::<HashMap<String, PluginHealth>, ()>("/status", &())
+            .await
+            .unwrap_or_default();
+        PluginManager { plugins, health }
     }
 
     pub fn names(&self) -> Vec<String> {
@@ -73,6 +80,29 @@ impl PluginManager {
         self.plugins.get(name)
     }
 
+    /// The server's last health check of `name` failed.
+    pub fn is_down(&self, name: &str) -> bool {
+        self.health
+            .get(name)
+            .is_some_and(|h| h.state == HealthState::Down)
+    }
+
+    /// Tooltip for a plugin that [`is_down`](Self::is_down).
+    pub fn down_reason(&self, name: &str) -> Option<String> {
+        let h = self
+            .health
+            .get(name)
+            .filter(|h| h.state == HealthState::Down)?;
+        let mut reason = format!("{} is not responding", self.display_name(name));
+        if let Some(e) = &h.error {
+            reason.push_str(&format!(": {}", e));
+        }
+        if let Some(seen) = h.last_seen {
+            reason.push_str(&format!(" (last seen {})", seen.format("%Y-%m-%d %H:%M")));
+        }
+        Some(reason)
+    }
+
     pub fn style(&self, name: &str) -> Style {
         self.plugins
             .get(name)
@@ -80,6 +110,27 @@ impl PluginManager {
             .unwrap_or_default()
     }
 
+    /// Whether `name` declares `capability`. Plugins that don't declare any
+    /// are assumed to, as the server still asks them.
+    pub fn supports(&self, name: &str, capability: Capability) -> bool {
+        self.plugins
+            .get(name)
+            .and_then(|m| m.capabilities.as_ref())
+            .is_none_or(|c| c.contains(&capability))
+    }
+
+    /// Display names of the plugins that don't declare `capability`, sorted.
+    pub fn lacking(&self, capability: Capability) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .plugins
+            .keys()
+            .filter(|name| !self.supports(name, capability))
+            .map(|name| self.display_name(name))
+            .collect();
+        names.sort();
+        names
+    }
+
     pub fn display_name(&self, name: &str) -> String {
         self.plugins
             .get(name)
@@ -111,7 +162,12 @@ impl PluginManager {
     /// Mount a plugin UI into the shadow root of `host`. If the plugin has
     /// no `web_entry`, or the dynamic import fails, a fallback title is
     /// shown instead.
-    pub async fn mount(&self, plugin_name: &str, host: web_sys::HtmlElement, event: CompressedEvent) {
+    pub async fn mount(
+        &self,
+        plugin_name: &str,
+        host: web_sys::HtmlElement,
+        event: CompressedEvent,
+    ) {
         let manifest = match self.plugins.get(plugin_name).cloned() {
             Some(m) => m,
             None => {
@@ -185,7 +241,8 @@ async fn dynamic_import_render(
     // `Timing` carries i64 nanoseconds which overflow JS Number precision
     // (2^53). Serialize i64/u64 as BigInt; the plugin's deserializer
     // (serde-wasm-bindgen::from_value) handles BigInt natively.
-    let serializer = serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true);
+    let serializer =
+        serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true);
     let ctx_value = ctx
         .serialize(&serializer)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
@@ -0,0 +1,172 @@
+//! `/search?q=<text>`: full-text search across every plugin that supports
+//! it. Picking a result opens its day on the timeline, at the hour of the
+//! event.
+
+use chrono::{SecondsFormat, Utc};
+use leptos::prelude::*;
+use leptos_router::hooks::{use_navigate, use_query_map};
+use leptos_router::NavigateOptions;
+
+use types::api::{APIError, Capability, CompressedEvent, SearchRequest, SearchResponse};
+use types::timing::Timing;
+
+use crate::api::api_request;
+use crate::events_display::failed_plugins;
+use crate::plugin_manager::PluginManager;
+use crate::wrappers::{Login, StyledView, TitleBar};
+
+#[component]
+pub fn Search() -> impl IntoView {
+    let query = use_query_map();
+    let text = Memo::new(move |_| query.with(|q| q.get("q")).unwrap_or_default());
+
+    let last_auth = RwSignal::new(Utc::now().timestamp_millis());
+    let results = LocalResource::new(move || {
+        let _ = last_auth.get();
+        let text = text.get();
+        async move {
+            if text.trim().is_empty() {
+                return Ok(SearchResponse::default());
+            }
+            let request = SearchRequest {
+                query: text,
+                limit: None,
+            };
+            api_request::<SearchResponse, _>("/search", &request).await
+        }
+    });
+
+    let plugin_manager_action = Action::new_local(|_: &()| async { PluginManager::load().await });
+    Effect::new(move |_| {
+        if plugin_manager_action.value().get_untracked().is_none() {
+            plugin_manager_action.dispatch(());
+        }
+    });
+    let plugin_manager =
+        Signal::derive(move || plugin_manager_action.value().get().unwrap_or_default());
+
+    let navigate = use_navigate();
+    let on_search = move |e: web_sys::Event| {
+        let value = event_target_value(&e);
+        let encoded: String = js_sys::encode_uri_component(&value).into();
+        navigate(
+            &format!("/search?q={}", encoded),
+            NavigateOptions::default(),
+        );
+    };
+
+    view! {
+        <StyledView>
+            <TitleBar subtitle=Signal::derive(|| Some("Search".to_string())) />
+            <input
+                class="searchInput"
+                type="search"
+                placeholder="Search all events"
+                prop:value=move || text.get()
+                on:change=on_search
+            />
+            {move || {
+                let unsearchable = plugin_manager.with(|m| m.lacking(Capability::Search));
+                (!unsearchable.is_empty()).then(|| view! {
+                    <div class="searchNote">
+                        {format!("Not searchable: {}", unsearchable.join(", "))}
+                    </div>
+                })
+            }}
+            {move || match results.get() {
+                None => view! { <div class="infoWrapper">Searching...</div> }.into_any(),
+                Some(Err(APIError::AuthenticationError)) => view! {
+                    <Login update_authentication=last_auth.write_only() />
+                }.into_any(),
+                Some(Err(e)) => view! {
+                    <div class="errorWrapper">{format!("Search failed: {}", e)}</div>
+                }.into_any(),
+                Some(Ok(res)) => {
+                    let failed = failed_plugins(&res.plugins);
+                    let any_failed = !failed.is_empty();
+                    let manager = plugin_manager.get();
+                    let mut groups: Vec<_> = res
+                        .results
+                        .into_iter()
+                        .filter(|(_, events)| !events.is_empty())
+                        .collect();
+                    groups.sort_by_key(|(name, _)| manager.display_name(name));
+                    let empty = groups.is_empty() && !text.with(|t| t.trim().is_empty());
+                    view! {
+                        <Show when=move || any_failed>
+                            <div class="pluginWarning">
+                                "Some plugins couldn't be searched: "
+                                {
+                                    let manager = plugin_manager.get();
+                                    failed
+                                        .iter()
+                                        .map(|(name, outcome)| format!("{} ({})", manager.display_name(name), outcome))
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                }
+                            </div>
+                        </Show>
+                        <Show when=move || empty>
+                            <div class="infoWrapper">No matches</div>
+                        </Show>
+                        <div class="searchResults">
+                            {groups
+                                .into_iter()
+                                .map(|(plugin, events)| view! {
+                                    <SearchGroup plugin=plugin events=events manager=plugin_manager />
+                                })
+                                .collect_view()}
+                        </div>
+                    }.into_any()
+                }
+            }}
+        </StyledView>
+    }
+}
+
+#[component]
+fn SearchGroup(
+    plugin: String,
+    events: Vec<CompressedEvent>,
+    #[prop(into)] manager: Signal<PluginManager>,
+) -> impl IntoView {
+    let style = manager.with(|m| m.style(&plugin));
+    let title = manager.with(|m| m.display_name(&plugin));
+    let icon_url = manager.with(|m| m.icon_url(&plugin));
+    let navigate = use_navigate();
+    view! {
+        <div class="searchGroup" style:background-color=style.bg()>
+            <div class="searchGroupTitle" style:color=style.text().to_string()>
+                <img src=icon_url class="searchGroupIcon" />
+                {title}
+            </div>
+            {events
+                .into_iter()
+                .map(|event| {
+                    let at = match &event.time {
+                        Timing::Instant(t) => *t,
+                        Timing::Range(r) => r.start,
+                    };
+                    let href = format!(
+                        "/timeline/{}?plugin={}",
+                        at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        plugin
+                    );
+                    let navigate = navigate.clone();
+                    view! {
+                        <div class="eventRow" style:border-top=format!("1px solid {}", style.light())>
+                            <button
+                                class="eventHeader"
+                                style:color=style.text().to_string()
+                                on:click=move |_| navigate(&href, NavigateOptions::default())
+                            >
+                                <h3>{event.title.clone()}</h3>
+                                <a>{event.time.display_with_day()}</a>
+                            </button>
+                        </div>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
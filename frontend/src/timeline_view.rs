@@ -1,21 +1,30 @@
-//! The top "timeline bar" with activity circles and a draggable pointer.
+//! The top "timeline bar" with stacked per-plugin activity bars and a
+//! draggable pointer.
 
 use chrono::{DateTime, SubsecRound, TimeDelta, Timelike, Utc};
 use leptos::prelude::*;
 use wasm_bindgen::JsCast;
 
-use types::timing::{Marker, TimeRange};
+use types::timing::{Marker, MarkerResolution, MarkersRequest, TimeRange};
 
 use crate::api::api_request;
+use crate::plugin_manager::PluginManager;
+
+/// Bucket size of the activity bars.
+const RESOLUTION: MarkerResolution = MarkerResolution::QuarterHour;
 
 #[component]
 pub fn TimelineBar(
     #[prop(into)] range: Signal<TimeRange>,
     #[prop(into)] on_range_pick: Callback<TimeRange>,
+    #[prop(into)] plugin_manager: Signal<PluginManager>,
 ) -> impl IntoView {
     let markers = LocalResource::new(move || {
-        let range = range.get();
-        async move { api_request::<Vec<Marker>, _>("/markers", &range).await }
+        let request = MarkersRequest {
+            range: range.get(),
+            resolution: RESOLUTION,
+        };
+        async move { api_request::<Vec<Marker>, _>("/markers", &request).await }
     });
 
     let dragging = RwSignal::new(false);
@@ -75,7 +84,9 @@ pub fn TimelineBar(
             on:touchcancel=move |_| { dragging.set(false); }
         >
             {move || markers.get().map(|res| match res {
-                Ok(markers) => circles_view(range.get_untracked(), markers),
+                Ok(markers) => {
+                    plugin_manager.with(|m| bars_view(range.get_untracked(), markers, m))
+                }
                 Err(e) => view! {
                     <div class="errorWrapper">{format!("Error: {}", e)}</div>
                 }.into_any(),
@@ -85,26 +96,51 @@ pub fn TimelineBar(
     }
 }
 
-fn circles_view(range: TimeRange, markers: Vec<Marker>) -> AnyView {
+/// One bar per bucket, as high as its share of the busiest bucket, split
+/// into a segment per plugin in that plugin's color.
+fn bars_view(range: TimeRange, markers: Vec<Marker>, manager: &PluginManager) -> AnyView {
     let max = markers.iter().map(|m| m.amount).max().unwrap_or(1);
+    let span = (
+        range.start.timestamp_millis() as f64,
+        range.end.timestamp_millis() as f64,
+    );
+    let width = RESOLUTION.step().num_milliseconds() as f64 / (span.1 - span.0) * 100.0;
 
     let views: Vec<_> = markers
-        .iter()
+        .into_iter()
         .map(|m| {
-            let width = format!("{}px", (m.amount as f64 / max as f64) * 100.0);
             let left = format!(
                 "{}%",
-                map_range(
-                    (
-                        range.start.timestamp_millis() as f64,
-                        range.end.timestamp_millis() as f64,
-                    ),
-                    (0.0, 100.0),
-                    m.time.timestamp_millis() as f64,
-                )
+                map_range(span, (0.0, 100.0), m.time.timestamp_millis() as f64)
             );
-            let top = format!("{}%", js_sys::Math::random() * 100.0);
-            view! { <div class="circle" style:width=width style:left=left style:top=top /> }
+            let height = format!("{}%", m.amount as f64 / max as f64 * 100.0);
+            let mut plugins: Vec<_> = m.plugins.into_iter().collect();
+            plugins.sort();
+            let segments: Vec<_> = plugins
+                .into_iter()
+                .map(|(plugin, amount)| {
+                    let color = manager.style(&plugin).bg();
+                    let title = format!("{}: {}", manager.display_name(&plugin), amount);
+                    view! {
+                        <div
+                            class="activitySegment"
+                            title=title
+                            style:flex-grow=amount.to_string()
+                            style:background-color=color
+                        />
+                    }
+                })
+                .collect();
+            view! {
+                <div
+                    class="activityBar"
+                    style:left=left
+                    style:width=format!("{}%", width)
+                    style:height=height
+                >
+                    {segments}
+                </div>
+            }
         })
         .collect();
 
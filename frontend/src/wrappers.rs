@@ -4,6 +4,11 @@
 use chrono::Utc;
 use leptos::prelude::*;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+
+use types::api::{APIError, LoginRequest};
+
+use crate::api::api_request;
 
 #[component]
 pub fn StyledView(children: Children) -> impl IntoView {
@@ -51,6 +56,7 @@ pub fn TitleBar(
 
 #[component]
 pub fn Login(update_authentication: WriteSignal<i64>) -> impl IntoView {
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
     view! {
         <div class="errorWrapper">
             <h3>Login</h3>
@@ -60,26 +66,49 @@ pub fn Login(update_authentication: WriteSignal<i64>) -> impl IntoView {
                 type="password"
                 placeholder="Password"
                 on:change=move |e| {
-                    let value = event_target_value(&e);
-                    set_password_cookie(value);
-                    update_authentication.set(Utc::now().timestamp_millis());
+                    let password = event_target_value(&e);
+                    spawn_local(async move {
+                        match api_request::<(), _>("/login", &LoginRequest { password }).await {
+                            Ok(()) => {
+                                error.set(None);
+                                update_authentication.set(Utc::now().timestamp_millis());
+                            }
+                            Err(APIError::AuthenticationError) => {
+                                error.set(Some("Wrong password".to_string()));
+                            }
+                            Err(e) => error.set(Some(format!("Login failed: {}", e))),
+                        }
+                    });
                 }
             />
+            {move || error.get().map(|e| view! { <p class="loginError">{e}</p> })}
         </div>
     }
 }
 
-fn set_password_cookie(password: String) {
-    let Some(doc) = web_sys::window().and_then(|w| w.document()) else {
-        return;
-    };
-    let Ok(html_doc) = doc.dyn_into::<web_sys::HtmlDocument>() else {
-        return;
+/// "Log out" and "Log out all sessions". Either way this browser is logged
+/// out, and `update_authentication` brings back the [`Login`].
+#[component]
+pub fn Logout(update_authentication: WriteSignal<i64>) -> impl IntoView {
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let logout = move |endpoint: &'static str| {
+        spawn_local(async move {
+            match api_request::<(), _>(endpoint, &()).await {
+                Ok(()) => {
+                    error.set(None);
+                    update_authentication.set(Utc::now().timestamp_millis());
+                }
+                Err(e) => error.set(Some(format!("Logout failed: {}", e))),
+            }
+        });
     };
-    let mut cookie = cookie::Cookie::new("pwd", password);
-    cookie.set_path("/");
-    let _ = html_doc.set_cookie(&format!(
-        "{}; expires=Fri, 31 Dec 9999 23:59:59 GMT; SameSite=None; Secure",
-        cookie
-    ));
+    view! {
+        <a class="searchLink" href="javascript:" on:click=move |_| logout("/logout")>
+            Log out
+        </a>
+        <a class="searchLink" href="javascript:" on:click=move |_| logout("/logout/all")>
+            Log out all sessions
+        </a>
+        {move || error.get().map(|e| view! { <p class="loginError">{e}</p> })}
+    }
 }
@@ -2,106 +2,353 @@
 
 use std::collections::HashMap;
 
-use chrono::{DateTime, SubsecRound, Timelike, Utc};
-use rocket::http::{CookieJar, Status};
+use chrono::{DateTime, Utc};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
 use rocket::post;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::State;
+use serde::{Deserialize, Serialize};
 
-use types::api::{APIError, APIResult, CompressedEvent};
-use types::timing::{Marker, TimeRange, Timing};
+use types::api::{
+    APIError, APIResult, EventsRequest, EventsResponse, LoginRequest, PluginHealth, SearchRequest,
+    SearchResponse,
+};
+use types::timing::{Marker, MarkersRequest, TimeRange, MAX_MARKER_BUCKETS};
 
+use crate::auth::{Access, Caller, PluginCaller, Scope};
 use crate::config::Config;
 use crate::plugin_registry::{PluginRegistry, RemoteManifest};
+use crate::reload::{Live, ReloadReport, ReloadTrigger, Reloader};
+use crate::session::{self, SessionStore, SESSION_COOKIE};
+use crate::throttle::{AuthThrottle, ClientAddr};
+use crate::tokens::{TokenInfo, TokenStore};
 
 // ---------- auth ----------
 
-fn auth(cookies: &CookieJar<'_>, config: &Config) -> APIResult<()> {
-    match cookies.get("pwd") {
-        Some(c) if c.value() == config.password => Ok(()),
-        _ => Err(APIError::AuthenticationError),
+#[post("/auth")]
+pub fn auth_request(caller: Caller) -> status::Custom<Json<APIResult<()>>> {
+    status::Custom(Status::Ok, Json(caller.require(Access::Any).map(|_| ())))
+}
+
+#[post("/login", data = "<request>")]
+pub async fn login(
+    request: Json<LoginRequest>,
+    client: ClientAddr,
+    cookies: &CookieJar<'_>,
+    config: &State<Live<Config>>,
+    sessions: &State<SessionStore>,
+    throttle: &State<AuthThrottle>,
+) -> status::Custom<Json<APIResult<()>>> {
+    if let Some(wait) = throttle.wait(&client, true) {
+        return status::Custom(
+            Status::TooManyRequests,
+            Json(Err(APIError::Custom(format!(
+                "too many failed attempts, try again in {}s",
+                wait.as_secs() + 1
+            )))),
+        );
+    }
+    let hash = config.load().password_hash.clone();
+    let password = request.into_inner().password;
+    let valid = tokio::task::spawn_blocking(move || session::verify_password(&hash, &password))
+        .await
+        .unwrap_or(false);
+    if !valid {
+        throttle.failure(&client, "login", true);
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    }
+    throttle.success(&client);
+    tracing::info!(ip = ?client.ip, forwarded_for = ?client.forwarded_for, "login");
+    match sessions.create().await {
+        Ok(token) => {
+            let max_age = rocket::time::Duration::seconds(sessions.ttl().num_seconds());
+            cookies.add(
+                Cookie::build((SESSION_COOKIE, token))
+                    .path("/")
+                    .http_only(true)
+                    .secure(true)
+                    .same_site(SameSite::Lax)
+                    .max_age(max_age),
+            );
+            status::Custom(Status::Ok, Json(Ok(())))
+        }
+        Err(e) => status::Custom(
+            Status::InternalServerError,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
     }
 }
 
-#[post("/auth")]
-pub fn auth_request(
+#[post("/logout")]
+pub async fn logout(
     cookies: &CookieJar<'_>,
-    config: &State<Config>,
+    sessions: &State<SessionStore>,
 ) -> status::Custom<Json<APIResult<()>>> {
-    status::Custom(Status::Ok, Json(auth(cookies, config)))
+    let result = match cookies.get(SESSION_COOKIE) {
+        Some(c) => sessions.revoke(c.value()).await,
+        None => Ok(()),
+    };
+    cookies.remove(Cookie::build(SESSION_COOKIE).path("/"));
+    match result {
+        Ok(()) => status::Custom(Status::Ok, Json(Ok(()))),
+        Err(e) => status::Custom(
+            Status::InternalServerError,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
+    }
+}
+
+/// End every session, including the caller's.
+#[post("/logout/all")]
+pub async fn logout_all(
+    caller: Caller,
+    cookies: &CookieJar<'_>,
+    sessions: &State<SessionStore>,
+) -> status::Custom<Json<APIResult<()>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    cookies.remove(Cookie::build(SESSION_COOKIE).path("/"));
+    match sessions.revoke_all().await {
+        Ok(()) => status::Custom(Status::Ok, Json(Ok(()))),
+        Err(e) => status::Custom(
+            Status::InternalServerError,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
+    }
 }
 
 // ---------- events (fan-out) ----------
 
-#[post("/events", data = "<range>")]
+/// Every event in the range, or one page per plugin if the request has a
+/// `limit` or `cursor`. Pages skip the event cache.
+#[post("/events", data = "<request>")]
 pub async fn events(
-    range: Json<TimeRange>,
-    cookies: &CookieJar<'_>,
-    config: &State<Config>,
-    registry: &State<PluginRegistry>,
-) -> status::Custom<Json<APIResult<HashMap<String, Vec<CompressedEvent>>>>> {
-    if let Err(e) = auth(cookies, config) {
-        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    request: Json<EventsRequest>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
+) -> status::Custom<Json<APIResult<EventsResponse>>> {
+    let grant = match caller.require(Access::ReadEvents) {
+        Ok(g) => g,
+        Err(e) => return status::Custom(Status::Unauthorized, Json(Err(e))),
+    };
+    let only = grant.plugin_filter();
+    let registry = registry.load();
+    if !request.is_paged() {
+        let events = registry.fan_out_events(&request.range, only.as_ref()).await;
+        return status::Custom(Status::Ok, Json(Ok(events)));
+    }
+    match registry.fan_out_events_page(&request, only.as_ref()).await {
+        Ok(events) => status::Custom(Status::Ok, Json(Ok(events))),
+        Err(e) => status::Custom(Status::BadRequest, Json(Err(e))),
     }
-    let events = registry.fan_out_events(&range).await;
-    status::Custom(Status::Ok, Json(Ok(events)))
 }
 
-// ---------- markers (derived from fan-out) ----------
+// ---------- markers (counted by plugins) ----------
 
-#[post("/markers", data = "<range>")]
+#[post("/markers", data = "<request>")]
 pub async fn markers(
-    range: Json<TimeRange>,
-    cookies: &CookieJar<'_>,
-    config: &State<Config>,
-    registry: &State<PluginRegistry>,
+    request: Json<MarkersRequest>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> status::Custom<Json<APIResult<Vec<Marker>>>> {
-    if let Err(e) = auth(cookies, config) {
-        return status::Custom(Status::Unauthorized, Json(Err(e)));
-    }
-    let events = registry.fan_out_events(&range).await;
-    let markers = derive_markers(events);
-    status::Custom(Status::Ok, Json(Ok(markers)))
-}
-
-fn derive_markers(all: HashMap<String, Vec<CompressedEvent>>) -> Vec<Marker> {
-    let mut buckets: HashMap<DateTime<Utc>, u32> = HashMap::new();
-    for (_plugin, events) in all {
-        for e in events {
-            let t = match e.time {
-                Timing::Instant(t) => t,
-                Timing::Range(r) => r.start,
-            };
-            let Some(hourly) = t
-                .round_subsecs(0)
-                .with_second(0)
-                .and_then(|x| x.with_minute(0))
-            else {
-                continue;
-            };
-            *buckets.entry(hourly).or_insert(0) += 1;
-        }
+    let grant = match caller.require(Access::ReadEvents) {
+        Ok(g) => g,
+        Err(e) => return status::Custom(Status::Unauthorized, Json(Err(e))),
+    };
+    let buckets = request.bucket_count();
+    if buckets > MAX_MARKER_BUCKETS {
+        return status::Custom(
+            Status::BadRequest,
+            Json(Err(APIError::Custom(format!(
+                "{} buckets requested, at most {} allowed",
+                buckets, MAX_MARKER_BUCKETS
+            )))),
+        );
     }
-    let mut out: Vec<_> = buckets
-        .into_iter()
-        .map(|(time, amount)| Marker { time, amount })
-        .collect();
-    out.sort_by(|a, b| b.amount.cmp(&a.amount));
-    out
+    let per_plugin = registry
+        .load()
+        .fan_out_markers(&request, grant.plugin_filter().as_ref())
+        .await;
+    status::Custom(Status::Ok, Json(Ok(crate::markers::combine(per_plugin))))
+}
+
+// ---------- full-text search (fan-out) ----------
+
+#[post("/search", data = "<request>")]
+pub async fn search(
+    request: Json<SearchRequest>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
+) -> status::Custom<Json<APIResult<SearchResponse>>> {
+    let grant = match caller.require(Access::ReadEvents) {
+        Ok(g) => g,
+        Err(e) => return status::Custom(Status::Unauthorized, Json(Err(e))),
+    };
+    let results = registry
+        .load()
+        .fan_out_search(&request, grant.plugin_filter().as_ref())
+        .await;
+    status::Custom(Status::Ok, Json(Ok(results)))
+}
+
+// ---------- event cache (called by plugins) ----------
+
+/// A plugin ingested new data in `range`; drop its cached events there.
+#[post("/cache/invalidate", data = "<range>")]
+pub fn invalidate_cache(
+    range: Json<TimeRange>,
+    caller: PluginCaller,
+    registry: &State<Live<PluginRegistry>>,
+) -> status::Custom<Json<APIResult<()>>> {
+    let Some(plugin) = caller.plugin else {
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    };
+    let dropped = registry.load().invalidate(&plugin, &range);
+    tracing::debug!(plugin = %plugin, dropped, "event cache invalidated for {:?}", *range);
+    status::Custom(Status::Ok, Json(Ok(())))
 }
 
 // ---------- manifest aggregation ----------
 
 #[post("/plugins")]
 pub async fn plugins(
-    cookies: &CookieJar<'_>,
-    config: &State<Config>,
-    registry: &State<PluginRegistry>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> status::Custom<Json<APIResult<Vec<RemoteManifest>>>> {
-    if let Err(e) = auth(cookies, config) {
-        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    let grant = match caller.require(Access::ReadEvents) {
+        Ok(g) => g,
+        Err(e) => return status::Custom(Status::Unauthorized, Json(Err(e))),
+    };
+    let mut manifests = registry.load().fan_out_manifests().await;
+    if let Some(only) = grant.plugin_filter() {
+        manifests.retain(|m| only.contains(&m.name));
     }
-    let manifests = registry.fan_out_manifests().await;
     status::Custom(Status::Ok, Json(Ok(manifests)))
 }
+
+// ---------- plugin health ----------
+
+/// Up/down state of each plugin, from the background `/health` checks.
+#[post("/status")]
+pub fn plugin_status(
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
+) -> status::Custom<Json<APIResult<HashMap<String, PluginHealth>>>> {
+    let grant = match caller.require(Access::ReadEvents) {
+        Ok(g) => g,
+        Err(e) => return status::Custom(Status::Unauthorized, Json(Err(e))),
+    };
+    let mut health = registry.load().health();
+    if let Some(only) = grant.plugin_filter() {
+        health.retain(|name, _| only.contains(name));
+    }
+    status::Custom(Status::Ok, Json(Ok(health)))
+}
+
+// ---------- API tokens (session only) ----------
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedToken {
+    pub name: String,
+    /// Shown once; only its hash is stored.
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    pub name: String,
+}
+
+#[post("/tokens")]
+pub fn tokens(
+    caller: Caller,
+    tokens: &State<TokenStore>,
+) -> status::Custom<Json<APIResult<Vec<TokenInfo>>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    status::Custom(Status::Ok, Json(Ok(tokens.list())))
+}
+
+#[post("/tokens/create", data = "<request>")]
+pub async fn create_token(
+    request: Json<CreateTokenRequest>,
+    caller: Caller,
+    tokens: &State<TokenStore>,
+) -> status::Custom<Json<APIResult<CreatedToken>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    let CreateTokenRequest {
+        name,
+        scopes,
+        expires,
+    } = request.into_inner();
+    match tokens.create(name.clone(), scopes, expires).await {
+        Ok(token) => status::Custom(Status::Ok, Json(Ok(CreatedToken { name, token }))),
+        Err(e) => status::Custom(
+            Status::BadRequest,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
+    }
+}
+
+#[post("/tokens/revoke", data = "<request>")]
+pub async fn revoke_token(
+    request: Json<RevokeTokenRequest>,
+    caller: Caller,
+    tokens: &State<TokenStore>,
+) -> status::Custom<Json<APIResult<()>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    match tokens.revoke(&request.name).await {
+        Ok(()) => status::Custom(Status::Ok, Json(Ok(()))),
+        Err(e) => status::Custom(
+            Status::BadRequest,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
+    }
+}
+
+// ---------- config reload (session only) ----------
+
+#[post("/config/reload")]
+pub async fn reload_config(
+    caller: Caller,
+    reloader: &State<Reloader>,
+) -> status::Custom<Json<APIResult<ReloadReport>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    let report = reloader.reload(ReloadTrigger::Api).await;
+    status::Custom(Status::Ok, Json(Ok(report)))
+}
+
+/// The most recent reload attempt, whatever triggered it.
+#[post("/config/last_reload")]
+pub fn last_reload(
+    caller: Caller,
+    reloader: &State<Reloader>,
+) -> status::Custom<Json<APIResult<Option<ReloadReport>>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    status::Custom(Status::Ok, Json(Ok(reloader.last())))
+}
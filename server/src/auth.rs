@@ -0,0 +1,359 @@
+//! Who is calling `/api/*`: a logged-in browser session or a scoped API
+//! token sent as `Authorization: Bearer <token>`, or, for the few routes
+//! plugins call back into, a plugin using its shared `[[plugin]].token`.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+
+use types::api::{APIError, APIResult};
+
+use crate::plugin_registry::PluginRegistry;
+use crate::reload::Live;
+use crate::session::{SessionStore, SESSION_COOKIE};
+use crate::throttle::{AuthThrottle, ClientAddr};
+use crate::tokens::TokenStore;
+
+/// What a route needs from the caller.
+#[derive(Debug, Clone, Copy)]
+pub enum Access<'a> {
+    /// `/api/auth`: any valid credential.
+    Any,
+    /// `/api/events`, `/api/markers`, `/api/plugins`.
+    ReadEvents,
+    /// A proxied call to `/api/plugin/<plugin>/...`. `write` for anything
+    /// but `GET`.
+    Proxy { plugin: &'a str, write: bool },
+    /// Session-only routes (token management, logging out everyone).
+    Admin,
+}
+
+/// One permission carried by an API token. Written as a string in
+/// `config.toml` and the token endpoints: `read_events`, `plugin:<name>`,
+/// `proxy_read`, `proxy_write`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    /// Events, markers and manifests of every plugin.
+    ReadEvents,
+    /// Events and manifest of one plugin, plus `GET`s through its proxy.
+    Plugin(String),
+    /// `GET`s through any plugin's proxy.
+    ProxyRead,
+    /// Any method through any plugin's proxy.
+    ProxyWrite,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read_events" => Ok(Scope::ReadEvents),
+            "proxy_read" => Ok(Scope::ProxyRead),
+            "proxy_write" => Ok(Scope::ProxyWrite),
+            _ => match s.strip_prefix("plugin:") {
+                Some(name) if !name.is_empty() => Ok(Scope::Plugin(name.to_string())),
+                _ => Err(format!("unknown scope: {}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::ReadEvents => write!(f, "read_events"),
+            Scope::Plugin(name) => write!(f, "plugin:{}", name),
+            Scope::ProxyRead => write!(f, "proxy_read"),
+            Scope::ProxyWrite => write!(f, "proxy_write"),
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(value: Scope) -> Self {
+        value.to_string()
+    }
+}
+
+/// A credential that checked out.
+#[derive(Debug, Clone)]
+pub enum Grant {
+    Session,
+    Token { name: String, scopes: Vec<Scope> },
+}
+
+impl Grant {
+    pub fn allows(&self, access: Access<'_>) -> bool {
+        let scopes = match self {
+            Grant::Session => return true,
+            Grant::Token { scopes, .. } => scopes,
+        };
+        match access {
+            Access::Any => true,
+            Access::ReadEvents => scopes
+                .iter()
+                .any(|s| matches!(s, Scope::ReadEvents | Scope::Plugin(_))),
+            Access::Proxy { plugin, write } => scopes.iter().any(|s| match s {
+                Scope::ProxyWrite => true,
+                Scope::ProxyRead => !write,
+                Scope::Plugin(name) => !write && name == plugin,
+                Scope::ReadEvents => false,
+            }),
+            Access::Admin => false,
+        }
+    }
+
+    /// Plugins whose events this caller may read; `None` means all of them.
+    pub fn plugin_filter(&self) -> Option<HashSet<String>> {
+        match self {
+            Grant::Session => None,
+            Grant::Token { scopes, .. } if scopes.contains(&Scope::ReadEvents) => None,
+            Grant::Token { scopes, .. } => Some(
+                scopes
+                    .iter()
+                    .filter_map(|s| match s {
+                        Scope::Plugin(name) => Some(name.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Request guard resolving the session cookie or bearer token. Only fails if
+/// the stores aren't managed; routes call [`Caller::require`] so they can
+/// answer with the usual JSON `APIError` body.
+pub struct Caller {
+    grant: Option<Grant>,
+}
+
+impl Caller {
+    pub fn require(&self, access: Access<'_>) -> APIResult<&Grant> {
+        match &self.grant {
+            Some(g) if g.allows(access) => Ok(g),
+            _ => Err(APIError::AuthenticationError),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Caller {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let (Outcome::Success(sessions), Outcome::Success(tokens)) = (
+            req.guard::<&State<SessionStore>>().await,
+            req.guard::<&State<TokenStore>>().await,
+        ) else {
+            return Outcome::Error((Status::InternalServerError, AuthError::StateMissing));
+        };
+
+        let grant = match bearer(req) {
+            Some(secret) => match throttled(req, "api token", || tokens.lookup(secret)) {
+                Ok(entry) => entry.map(|t| Grant::Token {
+                    name: t.name,
+                    scopes: t.scopes,
+                }),
+                Err(e) => return Outcome::Error(e),
+            },
+            None => match req.cookies().get(SESSION_COOKIE) {
+                Some(c) if sessions.is_valid(c.value()) => Some(Grant::Session),
+                _ => None,
+            },
+        };
+        Outcome::Success(Caller { grant })
+    }
+}
+
+/// Request guard for plugin → server calls: the plugin whose shared token
+/// came as the bearer. `None` if it matched no plugin.
+pub struct PluginCaller {
+    pub plugin: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PluginCaller {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Outcome::Success(registry) = req.guard::<&State<Live<PluginRegistry>>>().await else {
+            return Outcome::Error((Status::InternalServerError, AuthError::StateMissing));
+        };
+        let registry = registry.load();
+        let plugin = match bearer(req) {
+            Some(token) => {
+                match throttled(req, "plugin token", || {
+                    registry.by_token(token).map(|p| p.name.clone())
+                }) {
+                    Ok(plugin) => plugin,
+                    Err(e) => return Outcome::Error(e),
+                }
+            }
+            None => None,
+        };
+        Outcome::Success(PluginCaller { plugin })
+    }
+}
+
+/// Check a bearer secret with `lookup`, behind the brute-force throttle: a
+/// throttled address gets 429 without the secret being looked at, and a
+/// miss counts as a failure.
+fn throttled<T>(
+    req: &Request<'_>,
+    what: &str,
+    lookup: impl FnOnce() -> Option<T>,
+) -> Result<Option<T>, (Status, AuthError)> {
+    let Some(throttle) = req.rocket().state::<AuthThrottle>() else {
+        return Err((Status::InternalServerError, AuthError::StateMissing));
+    };
+    let client = ClientAddr::of(req);
+    if throttle.wait(&client, false).is_some() {
+        return Err((Status::TooManyRequests, AuthError::Throttled));
+    }
+    let found = lookup();
+    if found.is_none() {
+        throttle.failure(&client, what, false);
+    }
+    Ok(found)
+}
+
+fn bearer<'r>(req: &'r Request<'_>) -> Option<&'r str> {
+    req.headers()
+        .get_one("Authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    StateMissing,
+    /// Too many failed attempts from this address; see [`AuthThrottle`].
+    Throttled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(scopes: &[&str]) -> Grant {
+        Grant::Token {
+            name: "test".to_string(),
+            scopes: scopes.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    const READ: Access = Access::Proxy {
+        plugin: "a",
+        write: false,
+    };
+    const WRITE: Access = Access::Proxy {
+        plugin: "a",
+        write: true,
+    };
+    const READ_OTHER: Access = Access::Proxy {
+        plugin: "b",
+        write: false,
+    };
+
+    #[test]
+    fn grants_allow_only_what_their_scopes_say() {
+        let cases: &[(&[&str], Access, bool)] = &[
+            (&[], Access::Any, true),
+            (&[], Access::ReadEvents, false),
+            (&[], READ, false),
+            (&["read_events"], Access::ReadEvents, true),
+            (&["read_events"], READ, false),
+            (&["read_events"], Access::Admin, false),
+            (&["plugin:a"], Access::ReadEvents, true),
+            (&["plugin:a"], READ, true),
+            (&["plugin:a"], WRITE, false),
+            (&["plugin:a"], READ_OTHER, false),
+            (&["proxy_read"], READ, true),
+            (&["proxy_read"], READ_OTHER, true),
+            (&["proxy_read"], WRITE, false),
+            (&["proxy_read"], Access::ReadEvents, false),
+            (&["proxy_write"], WRITE, true),
+            (&["proxy_write"], READ, true),
+            (&["proxy_write", "read_events"], Access::Admin, false),
+        ];
+        for (scopes, access, allowed) in cases {
+            assert_eq!(
+                token(scopes).allows(*access),
+                *allowed,
+                "{:?} → {:?}",
+                scopes,
+                access
+            );
+        }
+        for access in [Access::Any, Access::ReadEvents, WRITE, Access::Admin] {
+            assert!(Grant::Session.allows(access), "session → {:?}", access);
+        }
+    }
+
+    #[test]
+    fn plugin_tokens_read_only_their_own_plugins() {
+        assert_eq!(token(&["read_events", "plugin:a"]).plugin_filter(), None);
+        assert_eq!(
+            token(&["plugin:a", "proxy_write"]).plugin_filter(),
+            Some(HashSet::from(["a".to_string()]))
+        );
+        assert_eq!(Grant::Session.plugin_filter(), None);
+    }
+
+    #[test]
+    fn require_answers_with_an_authentication_error() {
+        let caller = Caller {
+            grant: Some(token(&["proxy_read"])),
+        };
+        assert!(caller.require(READ).is_ok());
+        assert!(matches!(
+            caller.require(WRITE),
+            Err(APIError::AuthenticationError)
+        ));
+        let anonymous = Caller { grant: None };
+        assert!(matches!(
+            anonymous.require(Access::Any),
+            Err(APIError::AuthenticationError)
+        ));
+    }
+
+    #[test]
+    fn scopes_parse_from_and_print_as_their_config_form() {
+        for (text, scope) in [
+            ("read_events", Scope::ReadEvents),
+            ("proxy_read", Scope::ProxyRead),
+            ("proxy_write", Scope::ProxyWrite),
+            ("plugin:a", Scope::Plugin("a".to_string())),
+            ("plugin:a:b", Scope::Plugin("a:b".to_string())),
+        ] {
+            assert_eq!(text.parse::<Scope>(), Ok(scope.clone()));
+            assert_eq!(scope.to_string(), text);
+        }
+        for text in [
+            "",
+            "plugin:",
+            "plugin",
+            "admin",
+            "READ_EVENTS",
+            " read_events",
+        ] {
+            assert!(text.parse::<Scope>().is_err(), "{:?}", text);
+        }
+        let parsed: Vec<Scope> = serde_json::from_str(r#"["plugin:a", "proxy_read"]"#).unwrap();
+        assert_eq!(parsed, [Scope::Plugin("a".to_string()), Scope::ProxyRead]);
+        assert!(serde_json::from_str::<Vec<Scope>>(r#"["everything"]"#).is_err());
+    }
+}
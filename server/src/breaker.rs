@@ -0,0 +1,49 @@
+//! Per-plugin circuit breaker: after a run of failed calls the plugin is
+//! skipped for a cool-down, then gets one trial call. A success closes the
+//! circuit again.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+pub struct Breakers {
+    state: Mutex<HashMap<String, Breaker>>,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    /// Consecutive failed calls.
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breakers {
+    /// Whether `plugin` is cooling down and should be skipped.
+    pub fn is_open(&self, plugin: &str) -> bool {
+        let state = self.state.lock().expect("breaker lock");
+        state
+            .get(plugin)
+            .and_then(|b| b.open_until)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    pub fn record_success(&self, plugin: &str) {
+        self.state.lock().expect("breaker lock").remove(plugin);
+    }
+
+    pub fn record_failure(&self, plugin: &str, threshold: u32, cooldown: Duration) {
+        let mut state = self.state.lock().expect("breaker lock");
+        let breaker = state.entry(plugin.to_string()).or_default();
+        breaker.failures += 1;
+        if threshold > 0 && breaker.failures >= threshold {
+            tracing::warn!(
+                plugin,
+                failures = breaker.failures,
+                "circuit open, skipping plugin for {:?}",
+                cooldown
+            );
+            breaker.open_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
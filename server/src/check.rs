@@ -0,0 +1,261 @@
+//! Deployment check: contacts every `[[plugin]]` entry and says, step by
+//! step, whether it's wired up right. Mismatched names and tokens otherwise
+//! only show up as failed fan-out calls. Runs as `server check` and as
+//! `POST /api/check`.
+
+use std::path::{Component, Path};
+
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{post, State};
+use serde::Serialize;
+
+use types::api::{APIResult, Capability, EventsRequest, PLUGIN_PROTOCOL_VERSION};
+use types::timing::TimeRange;
+
+use crate::auth::{Access, Caller};
+use crate::config::Config;
+use crate::plugin_registry::{PluginHandle, PluginRegistry, RemoteManifest};
+use crate::reload::Live;
+use crate::request_id::WithRequestId;
+
+/// The verdict for one `[[plugin]]` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginCheck {
+    pub plugin: String,
+    /// No step failed.
+    pub ok: bool,
+    pub steps: Vec<CheckStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckStep {
+    /// `health`, `token`, `name`, `protocol`, `web_entry` or `events`.
+    pub step: &'static str,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum Verdict {
+    Pass(String),
+    Fail(String),
+    /// Not tried, because of an earlier failure or because it doesn't apply.
+    Skip(String),
+}
+
+impl Verdict {
+    fn is_fail(&self) -> bool {
+        matches!(self, Self::Fail(_))
+    }
+}
+
+/// Check every configured plugin, in config order. Calls bypass retries,
+/// the circuit breaker and the event cache.
+pub async fn check_all(registry: &PluginRegistry, data_dir: &Path) -> Vec<PluginCheck> {
+    let checks = registry
+        .all()
+        .iter()
+        .map(|plugin| check(registry, plugin, data_dir));
+    futures::future::join_all(checks).await
+}
+
+async fn check(registry: &PluginRegistry, plugin: &PluginHandle, data_dir: &Path) -> PluginCheck {
+    let mut steps = Vec::new();
+    let mut step = |step, verdict| steps.push(CheckStep { step, verdict });
+
+    let started = std::time::Instant::now();
+    match registry.health_for(plugin).await {
+        Ok(()) => step(
+            "health",
+            Verdict::Pass(format!("answered in {} ms", started.elapsed().as_millis())),
+        ),
+        Err(e) => step("health", Verdict::Fail(e.to_string())),
+    }
+
+    let manifest = match manifest(registry, plugin).await {
+        Ok(m) => {
+            step("token", Verdict::Pass("accepted on /manifest".to_string()));
+            Some(m)
+        }
+        Err(verdict) => {
+            step("token", verdict);
+            None
+        }
+    };
+
+    match &manifest {
+        None => step("name", Verdict::Skip("no manifest".to_string())),
+        Some(m) if m.name == plugin.name => step("name", Verdict::Pass(m.name.clone())),
+        Some(m) => step(
+            "name",
+            Verdict::Fail(format!(
+                "the plugin calls itself {:?}; rename the entry or the plugin's plugin.name",
+                m.name
+            )),
+        ),
+    }
+
+    match &manifest {
+        None => step("protocol", Verdict::Skip("no manifest".to_string())),
+        Some(m) => step("protocol", protocol_verdict(m)),
+    }
+
+    match manifest.as_ref().map(|m| m.web_entry.as_deref()) {
+        None => step("web_entry", Verdict::Skip("no manifest".to_string())),
+        Some(None) => step(
+            "web_entry",
+            Verdict::Skip("the plugin has no web UI".to_string()),
+        ),
+        Some(Some(entry)) if !is_plain_relative(entry) => step(
+            "web_entry",
+            Verdict::Fail(format!(
+                "{:?} is not a plain relative path inside the plugin's web directory",
+                entry
+            )),
+        ),
+        Some(Some(entry)) => {
+            let path = data_dir.join("plugin_web").join(&plugin.name).join(entry);
+            if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+                step("web_entry", Verdict::Pass(path.display().to_string()))
+            } else {
+                step(
+                    "web_entry",
+                    Verdict::Fail(format!("{} is missing", path.display())),
+                )
+            }
+        }
+    }
+
+    if manifest.is_none() {
+        step("events", Verdict::Skip("no manifest".to_string()));
+    } else {
+        let end = Utc::now();
+        let request = EventsRequest {
+            limit: Some(1),
+            ..EventsRequest::from(TimeRange {
+                start: end - Duration::days(1),
+                end,
+            })
+        };
+        match registry.page_for(plugin, &request).await {
+            Ok(page) => step(
+                "events",
+                Verdict::Pass(format!("{} events in the last day", page.events.len())),
+            ),
+            Err(e) => step("events", Verdict::Fail(e.to_string())),
+        }
+    }
+
+    PluginCheck {
+        plugin: plugin.name.clone(),
+        ok: !steps.iter().any(|s| s.verdict.is_fail()),
+        steps,
+    }
+}
+
+/// `web_entry` comes from the plugin, so it may only name something below
+/// `plugin_web/<name>`: no `..`, no root, no drive prefix.
+fn is_plain_relative(entry: &str) -> bool {
+    !entry.is_empty()
+        && Path::new(entry)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn protocol_verdict(manifest: &RemoteManifest) -> Verdict {
+    let capabilities = match &manifest.capabilities {
+        Some(c) if c.is_empty() => "no optional capabilities".to_string(),
+        Some(c) => c
+            .iter()
+            .map(Capability::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => "capabilities not declared".to_string(),
+    };
+    match manifest.protocol_version {
+        Some(PLUGIN_PROTOCOL_VERSION) => Verdict::Pass(format!(
+            "version {}; {}",
+            PLUGIN_PROTOCOL_VERSION, capabilities
+        )),
+        Some(v) => Verdict::Fail(format!(
+            "the plugin speaks version {}, this server {}",
+            v, PLUGIN_PROTOCOL_VERSION
+        )),
+        None => Verdict::Pass(format!("not declared (older SDK); {}", capabilities)),
+    }
+}
+
+/// `/manifest` with the entry's token, telling a rejected token apart from
+/// other failures. An unreachable plugin is a skip: `health` already failed.
+async fn manifest(
+    registry: &PluginRegistry,
+    plugin: &PluginHandle,
+) -> Result<RemoteManifest, Verdict> {
+    let fail = |e: String| Verdict::Fail(e);
+    let url = plugin
+        .base_url
+        .join("manifest")
+        .map_err(|e| fail(format!("bad url: {}", e)))?;
+    let res = registry
+        .client()
+        .get(url)
+        .bearer_auth(&plugin.token)
+        .with_request_id()
+        .timeout(plugin.manifest_timeout)
+        .send()
+        .await
+        .map_err(|e| Verdict::Skip(format!("unreachable: {}", e)))?;
+    match res.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Err(fail(format!(
+                "rejected ({}); the entry's token differs from the plugin's plugin.token",
+                res.status()
+            )))
+        }
+        status if !status.is_success() => {
+            return Err(fail(format!("/manifest returned {}", status)))
+        }
+        _ => {}
+    }
+    let text = res.text().await.map_err(|e| fail(e.to_string()))?;
+    serde_json::from_str(&text).map_err(|e| fail(format!("unreadable manifest: {}", e)))
+}
+
+#[post("/check")]
+pub async fn check_plugins(
+    caller: Caller,
+    config: &State<Live<Config>>,
+    registry: &State<Live<PluginRegistry>>,
+) -> status::Custom<Json<APIResult<Vec<PluginCheck>>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    let data_dir = config.load().data_dir.clone();
+    let checks = check_all(&registry.load(), &data_dir).await;
+    status::Custom(Status::Ok, Json(Ok(checks)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn web_entries_must_stay_inside_the_plugin_directory() {
+        for ok in ["index.html", "dist/index.html", "dist/./index.html"] {
+            assert!(is_plain_relative(ok), "{:?}", ok);
+        }
+        for bad in [
+            "",
+            "/etc/passwd",
+            "../other/index.html",
+            "dist/../../x",
+            "./index.html",
+        ] {
+            assert!(!is_plain_relative(bad), "{:?}", bad);
+        }
+    }
+}
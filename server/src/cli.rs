@@ -0,0 +1,190 @@
+//! Command line of the server binary. Without a subcommand it serves, so
+//! a plain `server` in the directory holding `config.toml` keeps working.
+//! Every flag can also be set from the environment (`TIMELINE_CONFIG`,
+//! `TIMELINE_FRONTEND_DIR`, `TIMELINE_PORT`), which is handier in systemd
+//! units.
+
+use std::io::{BufRead, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+
+use crate::auth::Scope;
+use crate::check::{self, Verdict};
+use crate::config::{ApiTokenEntry, Config};
+use crate::errors::ErrorLog;
+use crate::metrics::Metrics;
+use crate::plugin_registry::PluginRegistry;
+use crate::{session, tokens};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Timeline main server",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the server (the default).
+    Serve(ServeArgs),
+    /// Load and validate the config file, then exit.
+    CheckConfig(ConfigArg),
+    /// Contact every configured plugin and report whether its name, token,
+    /// protocol, web bundle and `/events` work; exits with 1 if any don't.
+    Check(ConfigArg),
+    /// Print the `password_hash` for a password typed at the prompt (not
+    /// echoed) or piped to stdin.
+    HashPassword,
+    /// Print a new random secret for a plugin `token`, or with `--name` a
+    /// ready `[[api_token]]` entry.
+    GenToken(GenTokenArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArg {
+    /// Path of `config.toml`; it's re-read from here on reload.
+    #[arg(long, short, env = "TIMELINE_CONFIG", default_value = "config.toml")]
+    pub config: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub config: ConfigArg,
+    /// Directory of the built frontend (`trunk build` output).
+    #[arg(
+        long,
+        env = "TIMELINE_FRONTEND_DIR",
+        default_value = "../frontend/dist"
+    )]
+    pub frontend_dir: PathBuf,
+    /// Listen here instead of on the config's `port`.
+    #[arg(long, short, env = "TIMELINE_PORT")]
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenTokenArgs {
+    /// Name of the API token; prints its config entry along with the secret.
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Scope of the API token (`read_events`, `plugin:<name>`, `proxy_read`,
+    /// `proxy_write`); may be repeated.
+    #[arg(long = "scope", requires = "name")]
+    pub scopes: Vec<Scope>,
+}
+
+impl Cli {
+    pub fn command(self) -> Command {
+        self.command.unwrap_or(Command::Serve(self.serve))
+    }
+}
+
+/// Read and validate the config, with errors that say which file is wrong.
+pub async fn load_config(path: &Path) -> anyhow::Result<Config> {
+    // `ConfigError` already spells out its cause; chaining it would repeat it.
+    let config = Config::load(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("unable to load {}: {}", path.display(), e))?;
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("invalid {}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+pub async fn check_config(args: ConfigArg) -> anyhow::Result<()> {
+    let config = load_config(&args.config).await?;
+    println!(
+        "{}: ok ({} plugins, {} API tokens)",
+        args.config.display(),
+        config.plugin.len(),
+        config.api_token.len()
+    );
+    Ok(())
+}
+
+pub async fn check(args: ConfigArg) -> anyhow::Result<()> {
+    let config = load_config(&args.config).await?;
+    // Failures are printed below; they don't belong in a running server's
+    // `errors.json` or its `error_report_url`.
+    let registry = PluginRegistry::new(
+        &config.plugin,
+        Arc::new(ErrorLog::in_memory()),
+        Arc::new(Metrics::new()),
+    );
+    let checks = check::check_all(&registry, &config.data_dir).await;
+    for plugin in &checks {
+        println!(
+            "{}: {}",
+            plugin.plugin,
+            if plugin.ok { "ok" } else { "FAILED" }
+        );
+        for step in &plugin.steps {
+            let (state, detail) = match &step.verdict {
+                Verdict::Pass(d) => ("ok", d),
+                Verdict::Fail(d) => ("FAIL", d),
+                Verdict::Skip(d) => ("skip", d),
+            };
+            println!("  {:<10} {:<5} {}", step.step, state, detail);
+        }
+    }
+    let failed = checks.iter().filter(|c| !c.ok).count();
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} plugins failed the check",
+        failed,
+        checks.len()
+    );
+    Ok(())
+}
+
+pub fn hash_password() -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    let password = if stdin.is_terminal() {
+        rpassword::prompt_password("Password: ").context("unable to read the password")?
+    } else {
+        let mut line = String::new();
+        stdin
+            .lock()
+            .read_line(&mut line)
+            .context("unable to read the password")?;
+        line.trim_end_matches(['\n', '\r']).to_string()
+    };
+    anyhow::ensure!(!password.is_empty(), "the password is empty");
+    let hash = session::hash_password(&password)
+        .map_err(|e| anyhow::anyhow!("unable to hash the password: {}", e))?;
+    println!("{}", hash);
+    Ok(())
+}
+
+pub fn gen_token(args: GenTokenArgs) -> anyhow::Result<()> {
+    let secret = tokens::generate_secret();
+    let Some(name) = args.name else {
+        println!("{}", secret);
+        return Ok(());
+    };
+    #[derive(Serialize)]
+    struct Entry {
+        api_token: [ApiTokenEntry; 1],
+    }
+    let entry = toml::to_string(&Entry {
+        api_token: [ApiTokenEntry {
+            name,
+            token_sha256: tokens::hash_secret(&secret),
+            scopes: args.scopes,
+            expires: None,
+        }],
+    })?;
+    println!("# Secret, shown once: {}\n{}", secret, entry);
+    Ok(())
+}
@@ -1,44 +1,264 @@
 //! `config.toml` shape for the main timeline server.
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use rocket::data::ByteUnit;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::auth::Scope;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub port: u16,
-    pub password: String,
+    /// Argon2 PHC string (`$argon2id$v=19$...`) of the login password.
+    pub password_hash: String,
+    /// How long a login session stays valid.
+    #[serde(default = "default_session_days")]
+    pub session_days: u32,
     #[serde(default = "default_data_dir")]
     pub data_dir: PathBuf,
+    /// Where new entries of the error log are forwarded
+    /// (`GET ?plugin=..&error=..`), like a plugin's own `error_report_url`.
     #[serde(default)]
     pub error_report_url: Option<Url>,
+    /// How often each plugin's `/health` is checked for `/api/status`. `0`
+    /// turns the checks off.
+    #[serde(default = "default_health_interval_secs")]
+    pub health_interval_secs: u64,
+    /// Limits on failed logins and bearer tokens.
+    #[serde(default)]
+    pub auth_limit: AuthLimit,
     #[serde(default)]
     pub plugin: Vec<PluginEntry>,
+    #[serde(default)]
+    pub api_token: Vec<ApiTokenEntry>,
 }
 
+/// `[auth_limit]`: how failed credentials (wrong password, unknown bearer
+/// token) slow down and lock out the address they came from.
 #[derive(Debug, Clone, Deserialize)]
+pub struct AuthLimit {
+    /// Failures from one address before it has to wait. The wait starts at
+    /// 1 s and doubles with every further failure.
+    #[serde(default = "default_free_attempts")]
+    pub free_attempts: u32,
+    /// Failures from one address that lock it out for `lockout_secs`.
+    /// Its failures are forgotten after that long without one.
+    #[serde(default = "default_lockout_after")]
+    pub lockout_after: u32,
+    #[serde(default = "default_lockout_secs")]
+    pub lockout_secs: u64,
+    /// Wrong passwords from all addresses together in one minute after
+    /// which password logins are refused for the rest of it.
+    #[serde(default = "default_global_failures_per_minute")]
+    pub global_failures_per_minute: u32,
+    /// Take the client address from the last `X-Forwarded-For` entry. Only
+    /// turn on behind a reverse proxy that sets it.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for AuthLimit {
+    fn default() -> Self {
+        Self {
+            free_attempts: default_free_attempts(),
+            lockout_after: default_lockout_after(),
+            lockout_secs: default_lockout_secs(),
+            global_failures_per_minute: default_global_failures_per_minute(),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct PluginEntry {
     /// Must match the plugin's own `plugin.name` — used in URLs
-    /// (`/api/plugin/<name>/...`, `/plugin_web/<name>/...`) and as the key
-    /// in the `/api/events` fan-out response.
+    /// (`/api/plugin/<name>/...`, `/plugin_web/<name>/...`), in file names
+    /// (`logs/<name>.log`) and as the key in the `/api/events` fan-out
+    /// response. Letters, digits, `-` and `_` only.
     pub name: String,
     /// Base URL the main server contacts the plugin on
     /// (e.g. `http://127.0.0.1:9001`).
     pub url: Url,
     /// Shared bearer token sent to the plugin.
     pub token: String,
+    /// Largest request body proxied to the plugin, e.g. `"64 MiB"`.
+    #[serde(default = "default_max_body")]
+    pub max_body: ByteUnit,
+    /// How long one `/events` call may take before it counts as timed out.
+    #[serde(default = "default_events_timeout_ms")]
+    pub events_timeout_ms: u64,
+    /// Same for `/manifest`.
+    #[serde(default = "default_manifest_timeout_ms")]
+    pub manifest_timeout_ms: u64,
+    /// Extra attempts after a timeout, connection error or 5xx. The wait
+    /// before each starts at `retry_backoff_ms` and doubles.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// After this many failed calls in a row the plugin is skipped (and
+    /// reported as degraded) for `breaker_cooldown_secs`. `0` disables this.
+    #[serde(default = "default_breaker_threshold")]
+    pub breaker_threshold: u32,
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub breaker_cooldown_secs: u64,
+    /// How long `/events` results are reused for the same range. `0`
+    /// disables caching for this plugin.
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// How long the plugin's manifest is served from memory before it's
+    /// checked again (with `If-None-Match`, so unchanged ones are cheap).
+    #[serde(default = "default_manifest_ttl_secs")]
+    pub manifest_ttl_secs: u64,
+    /// Program and arguments to launch the plugin with. Set → the server
+    /// starts the plugin itself and restarts it when it exits.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Directory to run `command` in (default: the server's own).
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    /// Extra environment variables for `command`.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// A scoped API token, sent as `Authorization: Bearer <secret>`. Also the
+/// on-disk shape of tokens minted through `/api/tokens/create`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenEntry {
+    pub name: String,
+    /// Hex SHA-256 of the secret (`echo -n <secret> | sha256sum`).
+    pub token_sha256: String,
+    pub scopes: Vec<Scope>,
+    /// Unset → never expires.
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
 }
 
 fn default_data_dir() -> PathBuf {
     PathBuf::from("./data")
 }
 
+fn default_max_body() -> ByteUnit {
+    ByteUnit::Mebibyte(8)
+}
+
+fn default_events_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_manifest_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_retries() -> u32 {
+    1
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    200
+}
+
+fn default_breaker_threshold() -> u32 {
+    3
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    60
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_manifest_ttl_secs() -> u64 {
+    300
+}
+
+fn default_health_interval_secs() -> u64 {
+    30
+}
+
+fn default_session_days() -> u32 {
+    30
+}
+
+fn default_free_attempts() -> u32 {
+    5
+}
+
+fn default_lockout_after() -> u32 {
+    15
+}
+
+fn default_lockout_secs() -> u64 {
+    900
+}
+
+fn default_global_failures_per_minute() -> u32 {
+    60
+}
+
 impl Config {
-    pub async fn load(path: &str) -> Result<Self, ConfigError> {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
         let raw = tokio::fs::read_to_string(path).await?;
         Ok(toml::from_str(&raw)?)
     }
+
+    /// Checks that parsing alone doesn't catch.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        argon2::PasswordHash::new(&self.password_hash).map_err(|e| {
+            ConfigError::Invalid(format!("password_hash is not a valid PHC string: {}", e))
+        })?;
+        if let Some(p) = self.plugin.iter().find(|p| !is_plugin_name(&p.name)) {
+            return Err(ConfigError::Invalid(format!(
+                "plugin name {:?} may only contain letters, digits, - and _",
+                p.name
+            )));
+        }
+        let mut names = HashSet::new();
+        if let Some(p) = self.plugin.iter().find(|p| !names.insert(&p.name)) {
+            return Err(ConfigError::Invalid(format!(
+                "plugin {} is listed twice",
+                p.name
+            )));
+        }
+        if let Some(p) = self
+            .plugin
+            .iter()
+            .find(|p| p.command.as_ref().is_some_and(Vec::is_empty))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "plugin {} has an empty command",
+                p.name
+            )));
+        }
+        if self.auth_limit.lockout_after <= self.auth_limit.free_attempts {
+            return Err(ConfigError::Invalid(
+                "auth_limit.lockout_after must be above free_attempts".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        if let Some(t) = self.api_token.iter().find(|t| !names.insert(&t.name)) {
+            return Err(ConfigError::Invalid(format!(
+                "api_token {} is listed twice",
+                t.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Safe as a URL segment and a file name: no `/`, `..` or the like.
+fn is_plugin_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -47,4 +267,29 @@ pub enum ConfigError {
     Io(#[from] std::io::Error),
     #[error("toml: {0}")]
     Toml(#[from] toml::de::Error),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$+63T4sL8J/Xe42rxtj4i6A$WTq3Prr/3OGPNUvWAl8ieRfIZkd6gNgrTEn0IJSUHdY";
+
+    fn with_plugin(name: &str) -> Config {
+        toml::from_str(&format!(
+            "port = 0\npassword_hash = {:?}\n[[plugin]]\nname = {:?}\nurl = \"http://127.0.0.1:1/\"\ntoken = \"t\"",
+            PASSWORD_HASH, name
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn plugin_names_cannot_leave_their_directories() {
+        assert!(with_plugin("media_scan-2").validate().is_ok());
+        for name in ["", "../logs", "a/b", "..", "a\\b", "a b"] {
+            assert!(with_plugin(name).validate().is_err(), "{:?}", name);
+        }
+    }
 }
@@ -0,0 +1,242 @@
+//! The central error log: what plugins send to `/api/errors/report`, plus
+//! the fan-out and proxy failures the server runs into itself.
+//!
+//! Kept in `<data_dir>/errors.json`, one entry per plugin and message. With
+//! `error_report_url` set, the first occurrence of each message is also
+//! forwarded there as `?plugin=&error=` (plus `&request_id=`), the same call
+//! plugins make to their own `error_report_url`. [`ErrorLog::in_memory`]
+//! does neither, for one-off runs such as `server check`.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::Client;
+use rocket::http::Status;
+use rocket::post;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+
+use types::api::{APIError, APIResult, ErrorReport, ErrorReportRequest};
+
+use crate::auth::{Access, Caller, PluginCaller};
+use crate::config::Config;
+use crate::reload::Live;
+use crate::store::{self, StoreError};
+
+/// Distinct messages kept; the least recently seen go first.
+const MAX_ENTRIES: usize = 500;
+/// Longer messages are cut to this many characters.
+const MAX_MESSAGE_CHARS: usize = 2000;
+/// Bursts of errors are written to disk together.
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct ErrorLog {
+    /// Unset for an in-memory log.
+    path: Option<PathBuf>,
+    /// Where `error_report_url` comes from; unset for an in-memory log.
+    config: Option<Live<Config>>,
+    entries: RwLock<Vec<ErrorReport>>,
+    /// Serializes writes of `errors.json`.
+    write_lock: tokio::sync::Mutex<()>,
+    save_pending: AtomicBool,
+    client: Client,
+}
+
+impl ErrorLog {
+    pub async fn open(path: impl Into<PathBuf>, config: Live<Config>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let entries: Vec<ErrorReport> = store::load_json(&path).await?;
+        Ok(Self::with(Some(path), Some(config), entries))
+    }
+
+    /// A log that's neither saved nor forwarded anywhere.
+    pub fn in_memory() -> Self {
+        Self::with(None, None, Vec::new())
+    }
+
+    fn with(
+        path: Option<PathBuf>,
+        config: Option<Live<Config>>,
+        entries: Vec<ErrorReport>,
+    ) -> Self {
+        Self {
+            path,
+            config,
+            entries: RwLock::new(entries),
+            write_lock: tokio::sync::Mutex::new(()),
+            save_pending: AtomicBool::new(false),
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("build http client"),
+        }
+    }
+
+    /// Count one occurrence of `message` for `plugin`, during request
+    /// `request_id` if any. Saving (and forwarding a new message) happens in
+    /// the background.
+    pub fn record(self: &Arc<Self>, plugin: &str, message: &str, request_id: Option<String>) {
+        let message: String = message.chars().take(MAX_MESSAGE_CHARS).collect();
+        let now = Utc::now();
+        let is_new = {
+            let mut entries = self.entries.write().expect("error log lock");
+            match entries
+                .iter_mut()
+                .find(|e| e.plugin == plugin && e.message == message)
+            {
+                Some(entry) => {
+                    entry.count += 1;
+                    entry.last_seen = now;
+                    entry.request_id = request_id.clone();
+                    false
+                }
+                None => {
+                    if entries.len() >= MAX_ENTRIES {
+                        if let Some(oldest) = entries
+                            .iter()
+                            .enumerate()
+                            .min_by_key(|(_, e)| e.last_seen)
+                            .map(|(i, _)| i)
+                        {
+                            entries.swap_remove(oldest);
+                        }
+                    }
+                    entries.push(ErrorReport {
+                        plugin: plugin.to_string(),
+                        message: message.clone(),
+                        first_seen: now,
+                        last_seen: now,
+                        count: 1,
+                        request_id: request_id.clone(),
+                    });
+                    true
+                }
+            }
+        };
+        if is_new {
+            self.forward(plugin, &message, request_id.as_deref());
+        }
+        self.schedule_save();
+    }
+
+    /// Every entry, most recently seen first.
+    pub fn list(&self) -> Vec<ErrorReport> {
+        let mut entries = self.entries.read().expect("error log lock").clone();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.last_seen));
+        entries
+    }
+
+    pub async fn clear(&self) -> Result<(), StoreError> {
+        self.entries.write().expect("error log lock").clear();
+        self.persist().await
+    }
+
+    fn schedule_save(self: &Arc<Self>) {
+        if self.path.is_none() || self.save_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let me = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            me.save_pending.store(false, Ordering::Release);
+            if let Err(e) = me.persist().await {
+                tracing::warn!("unable to save errors.json: {}", e);
+            }
+        });
+    }
+
+    async fn persist(&self) -> Result<(), StoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _guard = self.write_lock.lock().await;
+        let snapshot = self.entries.read().expect("error log lock").clone();
+        store::save_json(path, &snapshot).await
+    }
+
+    fn forward(&self, plugin: &str, message: &str, request_id: Option<&str>) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let Some(mut url) = config.load().error_report_url.clone() else {
+            return;
+        };
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("plugin", plugin)
+                .append_pair("error", message);
+            if let Some(id) = request_id {
+                query.append_pair("request_id", id);
+            }
+        }
+        let client = self.client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = client.get(url).send().await {
+                tracing::warn!("error webhook failed: {}", e);
+            }
+        });
+    }
+}
+
+// ---------- routes ----------
+
+/// Where plugins report their errors (with their own token).
+#[post("/errors/report", data = "<request>")]
+pub fn report(
+    request: Json<ErrorReportRequest>,
+    caller: PluginCaller,
+    errors: &State<Arc<ErrorLog>>,
+) -> status::Custom<Json<APIResult<()>>> {
+    let Some(plugin) = caller.plugin else {
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    };
+    let ErrorReportRequest {
+        message,
+        request_id,
+    } = request.into_inner();
+    if message.trim().is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            Json(Err(APIError::Custom("empty message".to_string()))),
+        );
+    }
+    errors.record(&plugin, &message, request_id);
+    status::Custom(Status::Ok, Json(Ok(())))
+}
+
+#[post("/errors")]
+pub fn errors(
+    caller: Caller,
+    errors: &State<Arc<ErrorLog>>,
+) -> status::Custom<Json<APIResult<Vec<ErrorReport>>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    status::Custom(Status::Ok, Json(Ok(errors.list())))
+}
+
+#[post("/errors/clear")]
+pub async fn clear_errors(
+    caller: Caller,
+    errors: &State<Arc<ErrorLog>>,
+) -> status::Custom<Json<APIResult<()>>> {
+    if let Err(e) = caller.require(Access::Admin) {
+        return status::Custom(Status::Unauthorized, Json(Err(e)));
+    }
+    match errors.clear().await {
+        Ok(()) => status::Custom(Status::Ok, Json(Ok(()))),
+        Err(e) => status::Custom(
+            Status::InternalServerError,
+            Json(Err(APIError::Custom(e.to_string()))),
+        ),
+    }
+}
@@ -0,0 +1,117 @@
+//! Short-lived cache of per-plugin `/events` results, keyed by plugin and
+//! exact time range. `/api/events` and `/api/markers` for the same day share
+//! one upstream call, and concurrent identical requests wait on the same
+//! in-flight fetch. Plugins drop stale entries through
+//! `/api/cache/invalidate` after ingesting new data.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt, Shared};
+
+use types::api::CompressedEvent;
+use types::timing::TimeRange;
+
+use crate::plugin_registry::FetchError;
+
+pub type Events = Arc<Vec<CompressedEvent>>;
+type Fetch = Shared<BoxFuture<'static, Result<Events, FetchError>>>;
+
+#[derive(Default)]
+pub struct EventCache {
+    slots: Mutex<HashMap<Key, Slot>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    plugin: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+struct Slot {
+    fetch: Fetch,
+    started: Instant,
+    ttl: Duration,
+}
+
+impl Slot {
+    /// Finished fetches are dropped once they failed or outlived their TTL;
+    /// in-flight ones are always kept so callers can join them.
+    fn expired(&self, now: Instant) -> bool {
+        match self.fetch.peek() {
+            None => false,
+            Some(Err(_)) => true,
+            Some(Ok(_)) => now.duration_since(self.started) >= self.ttl,
+        }
+    }
+}
+
+impl fmt::Debug for EventCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.slots.lock().map(|s| s.len()).unwrap_or_default();
+        f.debug_struct("EventCache").field("slots", &len).finish()
+    }
+}
+
+impl EventCache {
+    /// Cached events for `plugin` in `range`, or the result of `fetch`. A
+    /// zero `ttl` bypasses the cache.
+    pub async fn get_or_fetch<F>(
+        &self,
+        plugin: &str,
+        range: &TimeRange,
+        ttl: Duration,
+        fetch: F,
+    ) -> Result<Events, FetchError>
+    where
+        F: FnOnce() -> BoxFuture<'static, Result<Events, FetchError>>,
+    {
+        if ttl.is_zero() {
+            return fetch().await;
+        }
+        let key = Key {
+            plugin: plugin.to_string(),
+            start: range.start,
+            end: range.end,
+        };
+        let shared = {
+            let mut slots = self.slots.lock().expect("event cache lock");
+            let now = Instant::now();
+            slots.retain(|_, s| !s.expired(now));
+            slots
+                .entry(key)
+                .or_insert_with(|| Slot {
+                    fetch: fetch().shared(),
+                    started: now,
+                    ttl,
+                })
+                .fetch
+                .clone()
+        };
+        shared.await
+    }
+
+    /// Forget everything cached for `plugin`.
+    pub fn forget(&self, plugin: &str) {
+        let mut slots = self.slots.lock().expect("event cache lock");
+        slots.retain(|k, _| k.plugin != plugin);
+    }
+
+    /// Forget everything cached for `plugin` that overlaps `range`.
+    pub fn invalidate(&self, plugin: &str, range: &TimeRange) -> usize {
+        let mut slots = self.slots.lock().expect("event cache lock");
+        let before = slots.len();
+        slots.retain(|k, _| {
+            let cached = TimeRange {
+                start: k.start,
+                end: k.end,
+            };
+            k.plugin != plugin || !cached.overlap_range(range)
+        });
+        before - slots.len()
+    }
+}
@@ -0,0 +1,373 @@
+//! `/api/export`: a time range of events as a download, in a format other
+//! tools read (iCalendar, JSON Lines, CSV).
+//!
+//! Plugins are walked one after another, a page at a time, and each page is
+//! written out as it arrives, so a year of events never sits in memory.
+//! By then the response has started, so a plugin that fails is reported by
+//! a line in the file itself (see [`ExportFormat::failure`]).
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use futures::stream::BoxStream;
+use rocket::form;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, stream::TextStream};
+use rocket::{get, FromFormField, State};
+use sha2::{Digest, Sha256};
+
+use types::api::{CompressedEvent, EventOrder, EventsRequest};
+use types::timing::{TimeRange, Timing};
+
+use crate::auth::{Access, Caller};
+use crate::plugin_registry::PluginRegistry;
+use crate::reload::Live;
+use crate::request_id;
+
+/// Events asked from a plugin at once.
+const PAGE_SIZE: u32 = 1000;
+
+#[derive(FromFormField, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ics,
+    Jsonl,
+    Csv,
+}
+
+pub struct Export {
+    body: TextStream<BoxStream<'static, String>>,
+    content_type: ContentType,
+    filename: String,
+}
+
+impl<'r> response::Responder<'r, 'r> for Export {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> response::Result<'r> {
+        response::Response::build_from(self.body.respond_to(req)?)
+            .header(self.content_type)
+            .header(Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.filename),
+            ))
+            .ok()
+    }
+}
+
+/// Every event overlapping `start..end` (RFC 3339 timestamps or plain
+/// `YYYY-MM-DD` dates, in UTC) from the plugins in `plugins` (comma
+/// separated; all by default), in time order per plugin. A plugin that
+/// fails is recorded in the error log, and the file gets a line saying its
+/// events stop there.
+#[get("/export?<start>&<end>&<format>&<plugins>")]
+pub fn export(
+    start: &str,
+    end: &str,
+    format: form::Result<'_, ExportFormat>,
+    plugins: Option<&str>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
+) -> Result<Export, Status> {
+    let grant = caller
+        .require(Access::ReadEvents)
+        .map_err(|_| Status::Unauthorized)?;
+    let (Some(start), Some(end), Ok(format)) = (parse_time(start), parse_time(end), format) else {
+        return Err(Status::BadRequest);
+    };
+    let range = TimeRange { start, end };
+
+    let only = grant.plugin_filter();
+    let wanted: Option<HashSet<&str>> = plugins.map(|p| {
+        p.split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect()
+    });
+    let registry = registry.load();
+    let mut names: Vec<String> = registry
+        .all()
+        .iter()
+        .map(|p| p.name.clone())
+        .filter(|n| only.as_ref().is_none_or(|o| o.contains(n)))
+        .filter(|n| wanted.as_ref().is_none_or(|w| w.contains(n.as_str())))
+        .collect();
+    names.sort();
+
+    let filename = format!(
+        "timeline-{}-{}.{}",
+        range.start.format("%Y%m%d"),
+        range.end.format("%Y%m%d"),
+        format.extension()
+    );
+    Ok(Export {
+        body: TextStream(Box::pin(rows(registry, names, range, format))),
+        content_type: format.content_type(),
+        filename,
+    })
+}
+
+fn rows(
+    registry: Arc<PluginRegistry>,
+    plugins: Vec<String>,
+    range: TimeRange,
+    format: ExportFormat,
+) -> impl futures::Stream<Item = String> + Send + 'static {
+    let exported_at = Utc::now();
+    // The body is streamed after the handler returns, outside its scope.
+    let request_id = request_id::current();
+    rocket::response::stream::stream! {
+        if let Some(header) = format.header() {
+            yield header;
+        }
+        for name in plugins {
+            let Some(plugin) = registry.get(&name) else { continue };
+            let mut request = EventsRequest {
+                range: range.clone(),
+                limit: Some(PAGE_SIZE),
+                order: EventOrder::Asc,
+                cursor: None,
+            };
+            loop {
+                let fetch = async {
+                    let page = registry.events_page(plugin, &request).await;
+                    if let Err(e) = &page {
+                        registry.record_error(&name, format!("export failed: {}", e));
+                    }
+                    page
+                };
+                let page = match &request_id {
+                    Some(id) => request_id::scope(id.clone(), fetch).await,
+                    None => fetch.await,
+                };
+                let page = match page {
+                    Ok(page) => page,
+                    Err(e) => {
+                        yield format.failure(&name, &e.to_string());
+                        break;
+                    }
+                };
+                let chunk: String = page
+                    .events
+                    .iter()
+                    .map(|e| format.row(&name, e, exported_at))
+                    .collect();
+                yield chunk;
+                match page.next {
+                    Some(next) => request.cursor = Some(next),
+                    None => break,
+                }
+            }
+        }
+        if let Some(footer) = format.footer() {
+            yield footer;
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Ics => "ics",
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+        }
+    }
+
+    fn content_type(self) -> ContentType {
+        match self {
+            Self::Ics => ContentType::Calendar,
+            Self::Jsonl => ContentType::new("application", "x-ndjson"),
+            Self::Csv => ContentType::CSV,
+        }
+    }
+
+    fn header(self) -> Option<String> {
+        match self {
+            Self::Ics => Some(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//timeline//export//EN\r\n".to_string(),
+            ),
+            Self::Jsonl => None,
+            Self::Csv => Some("plugin,title,start,end,data\r\n".to_string()),
+        }
+    }
+
+    fn footer(self) -> Option<String> {
+        match self {
+            Self::Ics => Some("END:VCALENDAR\r\n".to_string()),
+            Self::Jsonl | Self::Csv => None,
+        }
+    }
+
+    /// The line marking that `plugin`'s events end early because of `error`:
+    /// an `X-` property for iCalendar, an `error` object for JSON Lines and a
+    /// row with only the plugin and the error as its title for CSV.
+    fn failure(self, plugin: &str, error: &str) -> String {
+        let message = format!("export of {} incomplete: {}", plugin, error);
+        match self {
+            Self::Ics => ics_fold(&format!("X-TIMELINE-EXPORT-ERROR:{}", ics_text(&message))),
+            Self::Jsonl => format!(
+                "{}\n",
+                serde_json::json!({ "plugin": plugin, "error": message })
+            ),
+            Self::Csv => format!("{},{},,,\r\n", csv_field(plugin), csv_field(&message)),
+        }
+    }
+
+    fn row(self, plugin: &str, event: &CompressedEvent, exported_at: DateTime<Utc>) -> String {
+        let (start, end) = match &event.time {
+            Timing::Instant(t) => (*t, None),
+            Timing::Range(r) => (r.start, Some(r.end)),
+        };
+        match self {
+            Self::Jsonl => {
+                let row = serde_json::json!({
+                    "plugin": plugin,
+                    "title": event.title,
+                    "start": rfc3339(start),
+                    "end": end.map(rfc3339),
+                    "data": event.data,
+                });
+                format!("{}\n", row)
+            }
+            Self::Csv => {
+                let fields = [
+                    plugin.to_string(),
+                    event.title.clone(),
+                    rfc3339(start),
+                    end.map(rfc3339).unwrap_or_default(),
+                    if event.data.is_null() {
+                        String::new()
+                    } else {
+                        event.data.to_string()
+                    },
+                ];
+                let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                format!("{}\r\n", fields.join(","))
+            }
+            Self::Ics => {
+                let mut lines = vec![
+                    "BEGIN:VEVENT".to_string(),
+                    format!("UID:{}@timeline", event_uid(plugin, event)),
+                    format!("DTSTAMP:{}", ics_time(exported_at)),
+                    format!("DTSTART:{}", ics_time(start)),
+                ];
+                if let Some(end) = end {
+                    lines.push(format!("DTEND:{}", ics_time(end)));
+                }
+                lines.push(format!("SUMMARY:{}", ics_text(&event.title)));
+                lines.push(format!("CATEGORIES:{}", ics_text(plugin)));
+                if !event.data.is_null() {
+                    lines.push(format!("DESCRIPTION:{}", ics_text(&event.data.to_string())));
+                }
+                lines.push("END:VEVENT".to_string());
+                lines.iter().map(|l| ics_fold(l)).collect()
+            }
+        }
+    }
+}
+
+/// RFC 3339, or a `YYYY-MM-DD` date taken as midnight UTC.
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn rfc3339(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Same event, same UID, so calendars importing an export twice update
+/// rather than duplicate.
+fn event_uid(plugin: &str, event: &CompressedEvent) -> String {
+    let mut hash = Sha256::new();
+    hash.update(plugin.as_bytes());
+    hash.update([0]);
+    hash.update(serde_json::to_vec(&event.time).unwrap_or_default());
+    hash.update([0]);
+    hash.update(event.title.as_bytes());
+    hex::encode(&hash.finalize()[..16])
+}
+
+fn ics_time(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// One content line, folded at 75 octets as RFC 5545 asks.
+fn ics_fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::plugin_registry::tests::{free_port, registry};
+
+    #[rocket::async_test]
+    async fn a_failing_plugin_is_marked_in_every_format() {
+        let unreachable = url::Url::parse(&format!("http://127.0.0.1:{}/", free_port())).unwrap();
+        let registry = Arc::new(registry(&[("gone", &unreachable)]));
+        let range = TimeRange {
+            start: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2026, 1, 2, 0, 0, 0).unwrap(),
+        };
+        for format in [ExportFormat::Ics, ExportFormat::Jsonl, ExportFormat::Csv] {
+            let body: String = rows(
+                registry.clone(),
+                vec!["gone".to_string()],
+                range.clone(),
+                format,
+            )
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+            let marker = body
+                .lines()
+                .find(|l| l.contains("export of gone incomplete"))
+                .unwrap_or_else(|| panic!("no failure line in {:?}: {:?}", format, body));
+            match format {
+                ExportFormat::Ics => {
+                    assert!(marker.starts_with("X-TIMELINE-EXPORT-ERROR:"));
+                    assert!(body.ends_with("END:VCALENDAR\r\n"));
+                }
+                ExportFormat::Jsonl => {
+                    let row: serde_json::Value = serde_json::from_str(marker).unwrap();
+                    assert_eq!(row["plugin"], "gone");
+                }
+                ExportFormat::Csv => assert!(marker.starts_with("gone,")),
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Live push of new events to the frontend.
+//!
+//! Plugins announce events they just stored via `/api/live/announce`; the
+//! server drops its cached events for that span and forwards them to every
+//! `/api/live` subscriber as Server-Sent Events.
+
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::{get, post, Shutdown, State};
+
+use types::api::{APIError, APIResult, CompressedEvent, LiveEvents};
+use types::timing::{TimeRange, Timing};
+
+use crate::auth::{Access, Caller, PluginCaller};
+use crate::plugin_registry::PluginRegistry;
+use crate::reload::Live;
+
+/// Messages buffered per subscriber before it's told to resync.
+const CHANNEL_CAPACITY: usize = 256;
+
+pub struct LiveHub {
+    tx: broadcast::Sender<Arc<LiveEvents>>,
+}
+
+impl Default for LiveHub {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl LiveHub {
+    /// Forward `events` to current subscribers; returns how many there were.
+    pub fn publish(&self, events: LiveEvents) -> usize {
+        self.tx.send(Arc::new(events)).unwrap_or(0)
+    }
+}
+
+/// Stream of `events` messages (a JSON [`LiveEvents`] each). A `resync`
+/// message means the client fell behind and some events were dropped;
+/// it should refetch `/api/events`.
+#[get("/live")]
+pub fn live(
+    caller: Caller,
+    hub: &State<LiveHub>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    let grant = caller
+        .require(Access::ReadEvents)
+        .map_err(|_| Status::Unauthorized)?;
+    let only = grant.plugin_filter();
+    let mut rx = hub.tx.subscribe();
+    Ok(EventStream! {
+        loop {
+            let msg = select! {
+                msg = rx.recv() => msg,
+                _ = &mut shutdown => break,
+            };
+            match msg {
+                Ok(m) if only.as_ref().is_none_or(|o| o.contains(&m.plugin)) => {
+                    yield Event::json(&*m).event("events");
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => yield Event::empty().event("resync"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// A plugin stored new or updated events. Called by
+/// `timeline_plugin_sdk::Notifier::announce`.
+#[post("/live/announce", data = "<body>")]
+pub fn announce(
+    body: Json<LiveEvents>,
+    caller: PluginCaller,
+    registry: &State<Live<PluginRegistry>>,
+    hub: &State<LiveHub>,
+) -> status::Custom<Json<APIResult<()>>> {
+    let Some(plugin) = caller.plugin else {
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    };
+    let LiveEvents { events, .. } = body.into_inner();
+    let Some(range) = span(&events) else {
+        return status::Custom(Status::Ok, Json(Ok(())));
+    };
+    registry.load().invalidate(&plugin, &range);
+    let listeners = hub.publish(LiveEvents {
+        plugin: plugin.clone(),
+        events,
+    });
+    tracing::debug!(plugin = %plugin, listeners, "live events published for {}", range);
+    status::Custom(Status::Ok, Json(Ok(())))
+}
+
+/// Smallest range covering every event in `events`.
+fn span(events: &[CompressedEvent]) -> Option<TimeRange> {
+    events
+        .iter()
+        .map(|e| match &e.time {
+            Timing::Instant(t) => (*t, *t),
+            Timing::Range(r) => (r.start, r.end),
+        })
+        .reduce(|(s1, e1), (s2, e2)| (s1.min(s2), e1.max(e2)))
+        .map(|(start, end)| TimeRange { start, end })
+}
@@ -1,20 +1,71 @@
 mod api;
+mod auth;
+mod breaker;
+mod check;
+mod cli;
 mod config;
+mod errors;
+mod event_cache;
+mod export;
+mod live;
+mod markers;
+mod metrics;
 mod plugin_registry;
 mod proxy;
+mod reload;
+mod request_id;
+mod session;
+mod store;
+mod supervisor;
+mod throttle;
+mod tokens;
 
 use std::io;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 
+use anyhow::Context;
+use clap::Parser;
+
+use rocket::fairing::AdHoc;
 use rocket::fs::{FileServer, NamedFile, Options};
 use rocket::response::{content, status};
 use rocket::{catch, catchers, routes, Request};
 
-use crate::config::Config;
+use crate::cli::{Cli, Command, ServeArgs};
+use crate::errors::ErrorLog;
+use crate::live::LiveHub;
+use crate::metrics::Metrics;
 use crate::plugin_registry::PluginRegistry;
+use crate::reload::{Live, Reloader};
+use crate::session::SessionStore;
+use crate::supervisor::Supervisor;
+use crate::throttle::AuthThrottle;
+use crate::tokens::TokenStore;
+
+/// Where the frontend bundle is served from, for the SPA fallback.
+struct FrontendDir(PathBuf);
+
+#[rocket::main]
+async fn main() -> ExitCode {
+    let result = match Cli::parse().command() {
+        Command::Serve(args) => serve(args).await,
+        Command::CheckConfig(args) => cli::check_config(args).await,
+        Command::Check(args) => cli::check(args).await,
+        Command::HashPassword => cli::hash_password(),
+        Command::GenToken(args) => cli::gen_token(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-#[rocket::launch]
-async fn rocket() -> _ {
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
     let _ = tracing::subscriber::set_global_default(
         tracing_subscriber::FmtSubscriber::builder()
             .with_env_filter(
@@ -24,52 +75,126 @@ async fn rocket() -> _ {
             .finish(),
     );
 
-    let config = Config::load("config.toml")
-        .await
-        .unwrap_or_else(|e| panic!("unable to load config.toml: {}", e));
+    let config_path = args.config.config;
+    let config = cli::load_config(&config_path).await?;
+    // `FileServer` would panic on it.
+    anyhow::ensure!(
+        args.frontend_dir.is_dir(),
+        "frontend directory {} not found; build it or pass --frontend-dir",
+        args.frontend_dir.display()
+    );
 
     tokio::fs::create_dir_all(&config.data_dir)
         .await
-        .unwrap_or_else(|e| panic!("unable to create data_dir: {}", e));
+        .with_context(|| format!("unable to create {}", config.data_dir.display()))?;
     tokio::fs::create_dir_all(config.data_dir.join("plugin_web"))
         .await
         .ok();
 
-    let registry = PluginRegistry::new(&config.plugin);
-    tracing::info!(count = config.plugin.len(), "plugins registered");
-
+    let sessions = SessionStore::open(
+        config.data_dir.join("sessions.json"),
+        chrono::Duration::days(config.session_days.into()),
+    )
+    .await
+    .context("unable to load sessions")?;
     let plugin_web_root = config.data_dir.join("plugin_web");
-    let figment = rocket::Config::figment().merge(("port", config.port));
+    let figment = rocket::Config::figment().merge(("port", args.port.unwrap_or(config.port)));
+    let tokens_path = config.data_dir.join("tokens.json");
+    let errors_path = config.data_dir.join("errors.json");
+
+    let config = Live::new(config);
+    let errors = ErrorLog::open(errors_path, config.clone())
+        .await
+        .map(Arc::new)
+        .context("unable to load the error log")?;
+    let metrics = Arc::new(Metrics::new());
+    let registry = PluginRegistry::new(&config.load().plugin, errors.clone(), metrics.clone());
+    tracing::info!(count = config.load().plugin.len(), "plugins registered");
+    let registry = Live::new(registry);
+    let throttle = AuthThrottle::new(config.clone());
+    let tokens = TokenStore::open(tokens_path, config.clone())
+        .await
+        .context("unable to load tokens")?;
+    let supervisor = Supervisor::new(config.load().data_dir.join("logs"));
+    supervisor.apply(&config.load().plugin).await;
+    let reloader = Reloader::new(
+        config_path,
+        config.clone(),
+        registry.clone(),
+        supervisor.clone(),
+    );
+    tokio::spawn(reloader.clone().watch());
+    tokio::spawn(PluginRegistry::watch_health(
+        registry.clone(),
+        config.clone(),
+    ));
 
     rocket::custom(figment)
         .register("/", catchers![not_found])
         .manage(config)
         .manage(registry)
-        .mount("/", FileServer::from("../frontend/dist/").rank(20))
+        .manage(sessions)
+        .manage(tokens)
+        .manage(throttle)
+        .manage(errors)
+        .manage(metrics)
+        .manage(reloader)
+        .manage(LiveHub::default())
+        .manage(FrontendDir(args.frontend_dir.clone()))
+        .attach(request_id::Tag)
+        .attach(AdHoc::on_shutdown("Plugin supervisor", |_| {
+            Box::pin(async move { supervisor.shutdown().await })
+        }))
+        .mount("/", FileServer::from(args.frontend_dir).rank(20))
+        .mount("/", routes![metrics::metrics])
         .mount(
             "/plugin_web",
             FileServer::new(plugin_web_root, Options::Index | Options::DotFiles).rank(5),
         )
         .mount(
             "/api",
-            routes![
+            request_id::scoped(routes![
                 api::auth_request,
+                api::login,
+                api::logout,
+                api::logout_all,
                 api::events,
                 api::markers,
+                api::search,
                 api::plugins,
+                api::plugin_status,
+                api::invalidate_cache,
+                api::tokens,
+                api::create_token,
+                api::revoke_token,
+                api::reload_config,
+                api::last_reload,
+                check::check_plugins,
+                errors::report,
+                errors::errors,
+                errors::clear_errors,
+                export::export,
+                live::live,
+                live::announce,
                 proxy::proxy_get,
                 proxy::proxy_post,
                 proxy::proxy_put,
                 proxy::proxy_delete,
-            ],
+            ]),
         )
+        .launch()
+        .await?;
+    Ok(())
 }
 
 #[catch(404)]
 async fn not_found(
-    _req: &Request<'_>,
+    req: &Request<'_>,
 ) -> Result<status::Accepted<content::RawHtml<NamedFile>>, io::Error> {
-    let path = PathBuf::from("../frontend/dist/index.html");
+    let dir = req.rocket().state::<FrontendDir>().map(|d| d.0.as_path());
+    let path = dir
+        .unwrap_or("../frontend/dist".as_ref())
+        .join("index.html");
     match NamedFile::open(path).await {
         Ok(f) => Ok(status::Accepted(content::RawHtml(f))),
         Err(e) => Err(e),
@@ -0,0 +1,78 @@
+//! Event counts per time bucket for `/api/markers`.
+//!
+//! Plugins count their own events (SDK `/markers`); [`count`] does the same
+//! over fetched events for plugins that can't, and [`combine`] merges the
+//! per-plugin results.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use types::api::CompressedEvent;
+use types::timing::{Marker, MarkersRequest, Timing};
+
+/// Count `events` per bucket of `request`, in time order. A range event
+/// counts towards every bucket it overlaps. Empty buckets are left out.
+pub fn count(events: &[CompressedEvent], request: &MarkersRequest) -> Vec<Marker> {
+    let range = &request.range;
+    let Some(step_ns) = request
+        .resolution
+        .step()
+        .num_nanoseconds()
+        .filter(|&n| n > 0)
+    else {
+        return Vec::new();
+    };
+    let bucket_of = |t: DateTime<Utc>| {
+        (t - range.start)
+            .num_nanoseconds()
+            .map(|ns| ns.div_euclid(step_ns))
+    };
+    let last = bucket_of(range.end - TimeDelta::nanoseconds(1)).unwrap_or(0);
+
+    let mut buckets: BTreeMap<i64, u32> = BTreeMap::new();
+    for e in events {
+        let (start, end) = match &e.time {
+            Timing::Instant(t) => (*t, *t),
+            // The end is exclusive, like `TimeRange::includes`.
+            Timing::Range(r) => (r.start, (r.end - TimeDelta::nanoseconds(1)).max(r.start)),
+        };
+        let (Some(first), Some(until)) = (bucket_of(start), bucket_of(end)) else {
+            continue;
+        };
+        for bucket in first.max(0)..=until.min(last) {
+            *buckets.entry(bucket).or_insert(0) += 1;
+        }
+    }
+    buckets
+        .into_iter()
+        .map(|(bucket, amount)| Marker {
+            time: range.start + TimeDelta::nanoseconds(bucket * step_ns),
+            amount,
+            plugins: HashMap::new(),
+        })
+        .collect()
+}
+
+/// Merge each plugin's markers into one list in time order, with
+/// `Marker::plugins` broken down by plugin name.
+pub fn combine(per_plugin: HashMap<String, Vec<Marker>>) -> Vec<Marker> {
+    let mut buckets: BTreeMap<DateTime<Utc>, HashMap<String, u32>> = BTreeMap::new();
+    for (plugin, markers) in per_plugin {
+        for m in markers.into_iter().filter(|m| m.amount > 0) {
+            *buckets
+                .entry(m.time)
+                .or_default()
+                .entry(plugin.clone())
+                .or_insert(0) += m.amount;
+        }
+    }
+    buckets
+        .into_iter()
+        .map(|(time, plugins)| Marker {
+            time,
+            amount: plugins.values().sum(),
+            plugins,
+        })
+        .collect()
+}
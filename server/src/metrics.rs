@@ -0,0 +1,168 @@
+//! `GET /metrics`: Prometheus counters for the calls the server makes to its
+//! plugins. Needs any valid credential, so scrape with an API token as the
+//! bearer.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use prometheus::core::{Collector, MetricVec, MetricVecBuilder};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use rocket::http::{ContentType, Status};
+use rocket::{get, State};
+
+use crate::auth::{Access, Caller};
+
+pub struct Metrics {
+    registry: Registry,
+    /// Plugin calls by plugin and kind (`events`, `markers`, ...), retries
+    /// included.
+    pub plugin_call_seconds: HistogramVec,
+    /// Failed plugin calls by plugin, kind and reason (`timeout`,
+    /// `unavailable`, `failed`, `degraded`).
+    pub plugin_call_errors: IntCounterVec,
+    /// Proxied calls by plugin and the status the plugin answered with, or
+    /// `error` when it couldn't be reached.
+    pub proxy_responses: IntCounterVec,
+    /// 1 if the plugin's latest `/health` check succeeded.
+    pub plugin_up: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("timeline".to_string()), None).expect("metrics registry");
+        let plugin_call_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "plugin_call_duration_seconds",
+                "Time taken by calls to plugins",
+            ),
+            &["plugin", "call"],
+        )
+        .expect("metric");
+        let plugin_call_errors = IntCounterVec::new(
+            Opts::new("plugin_call_errors_total", "Failed calls to plugins"),
+            &["plugin", "call", "reason"],
+        )
+        .expect("metric");
+        let proxy_responses = IntCounterVec::new(
+            Opts::new("proxy_responses_total", "Proxied plugin calls by status"),
+            &["plugin", "status"],
+        )
+        .expect("metric");
+        let plugin_up = IntGaugeVec::new(
+            Opts::new("plugin_up", "Whether the plugin's last health check passed"),
+            &["plugin"],
+        )
+        .expect("metric");
+        for collector in [
+            Box::new(plugin_call_seconds.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(plugin_call_errors.clone()),
+            Box::new(proxy_responses.clone()),
+            Box::new(plugin_up.clone()),
+        ] {
+            registry.register(collector).expect("register metric");
+        }
+        Self {
+            registry,
+            plugin_call_seconds,
+            plugin_call_errors,
+            proxy_responses,
+            plugin_up,
+        }
+    }
+
+    /// Drop every series of a plugin that's no longer configured, so it
+    /// isn't exported as stale data.
+    pub fn forget(&self, plugin: &str) {
+        forget_in(&self.plugin_call_seconds, plugin);
+        forget_in(&self.plugin_call_errors, plugin);
+        forget_in(&self.proxy_responses, plugin);
+        forget_in(&self.plugin_up, plugin);
+    }
+
+    fn render(&self) -> String {
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut out)
+            .expect("encode metrics");
+        String::from_utf8(out).unwrap_or_default()
+    }
+}
+
+/// Remove the series of `vec` labelled with `plugin`, whatever their other
+/// labels.
+fn forget_in<T: MetricVecBuilder>(vec: &MetricVec<T>, plugin: &str) {
+    for family in vec.collect() {
+        for metric in family.get_metric() {
+            let labels: HashMap<&str, &str> = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name(), l.get_value()))
+                .collect();
+            if labels.get("plugin") == Some(&plugin) {
+                let _ = vec.remove(&labels);
+            }
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+#[get("/metrics")]
+pub fn metrics(
+    caller: Caller,
+    metrics: &State<Arc<Metrics>>,
+) -> Result<(ContentType, String), Status> {
+    caller
+        .require(Access::Any)
+        .map_err(|_| Status::Unauthorized)?;
+    Ok((ContentType::Plain, metrics.render()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgotten_plugins_leave_no_series() {
+        let metrics = Metrics::new();
+        for plugin in ["gone", "kept"] {
+            metrics
+                .plugin_call_seconds
+                .with_label_values(&[plugin, "events"])
+                .observe(0.1);
+            metrics
+                .plugin_call_errors
+                .with_label_values(&[plugin, "events", "degraded"])
+                .inc();
+            metrics
+                .proxy_responses
+                .with_label_values(&[plugin, "200"])
+                .inc();
+            metrics.plugin_up.with_label_values(&[plugin]).set(1);
+        }
+
+        metrics.forget("gone");
+
+        let rendered = metrics.render();
+        assert!(!rendered.contains("plugin=\"gone\""), "{}", rendered);
+        assert!(
+            rendered.contains("timeline_plugin_up{plugin=\"kept\"} 1"),
+            "{}",
+            rendered
+        );
+    }
+}
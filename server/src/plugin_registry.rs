@@ -1,29 +1,66 @@
 //! Talks to the configured plugin processes over HTTP.
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
+use futures::FutureExt;
 use reqwest::Client;
+use rocket::data::ByteUnit;
 use serde::{Deserialize, Serialize};
 
-use types::api::{APIError, APIResult, CompressedEvent};
-use types::timing::TimeRange;
+use types::api::{
+    APIError, APIResult, Capability, CompressedEvent, EventsPage, EventsRequest, EventsResponse,
+    HealthState, PluginHealth, PluginOutcome, PluginStatus, SearchRequest, SearchResponse,
+    PLUGIN_PROTOCOL_VERSION,
+};
+use types::timing::{Marker, MarkersRequest, TimeRange};
 
-use crate::config::PluginEntry;
+use crate::breaker::Breakers;
+use crate::config::{Config, PluginEntry};
+use crate::errors::ErrorLog;
+use crate::event_cache::EventCache;
+use crate::markers;
+use crate::metrics::Metrics;
+use crate::reload::Live;
+use crate::request_id::{self, WithRequestId};
+use crate::tokens;
 
 #[derive(Debug, Clone)]
 pub struct PluginRegistry {
     plugins: Vec<PluginHandle>,
     by_name: HashMap<String, usize>,
     client: Client,
+    /// For `/api/plugin/...`: no overall timeout, so long downloads and
+    /// uploads can stream.
+    proxy_client: Client,
+    /// Latest manifest of each plugin, see [`PluginRegistry::manifest`].
+    manifests: Arc<RwLock<HashMap<String, CachedManifest>>>,
+    breakers: Arc<Breakers>,
+    cache: Arc<EventCache>,
+    /// Latest `/health` result per plugin, from [`PluginRegistry::poll_health`].
+    health: Arc<RwLock<HashMap<String, PluginHealth>>>,
+    /// Where failed fan-out and proxy calls are recorded.
+    errors: Arc<ErrorLog>,
+    metrics: Arc<Metrics>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PluginHandle {
     pub name: String,
     pub base_url: url::Url,
     pub token: String,
+    pub max_body: ByteUnit,
+    pub events_timeout: Duration,
+    pub manifest_timeout: Duration,
+    pub retries: u32,
+    pub retry_backoff: Duration,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    pub cache_ttl: Duration,
+    pub manifest_ttl: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -36,32 +73,149 @@ pub struct RemoteManifest {
     pub icon: Option<String>,
     #[serde(default)]
     pub web_entry: Option<String>,
+    #[serde(default)]
+    pub routes: Vec<RouteRule>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub sdk_version: Option<String>,
+    /// Plugin protocol version; unset for plugins from before it existed.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Optional protocol parts the plugin implements. Unset for plugins from
+    /// before capabilities, which are called for everything and may 404.
+    #[serde(default)]
+    pub capabilities: Option<Vec<Capability>>,
+}
+
+impl RemoteManifest {
+    /// Whether the plugin declares `capability`, or predates declaring any.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_none_or(|c| c.contains(&capability))
+    }
+}
+
+/// A fetched manifest and what's needed to revalidate it.
+#[derive(Debug, Clone)]
+struct CachedManifest {
+    manifest: RemoteManifest,
+    etag: Option<String>,
+    checked: Instant,
+    /// Why the manifest was rejected; it isn't served then.
+    rejected: Option<String>,
+}
+
+impl CachedManifest {
+    fn result(self) -> Result<RemoteManifest, FetchError> {
+        match self.rejected {
+            None => Ok(self.manifest),
+            Some(why) => Err(APIError::PluginError(format!("manifest rejected: {}", why)).into()),
+        }
+    }
+}
+
+/// Answer to a manifest fetch.
+enum ManifestFetch {
+    Changed {
+        manifest: Box<RemoteManifest>,
+        etag: Option<String>,
+    },
+    /// `304`: the cached one is still current.
+    Unchanged,
+}
+
+/// Mirrors `timeline_plugin_sdk::RouteRule`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteRule {
+    pub prefix: String,
+    pub access: RouteAccess,
+}
+
+/// Mirrors `timeline_plugin_sdk::RouteAccess`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteAccess {
+    Public,
+    Signed,
 }
 
 impl PluginRegistry {
-    pub fn new(entries: &[PluginEntry]) -> Self {
-        let plugins = entries
+    pub fn new(entries: &[PluginEntry], errors: Arc<ErrorLog>, metrics: Arc<Metrics>) -> Self {
+        // Timeouts are set per request from the plugin's entry.
+        let client = Client::builder().build().expect("build http client");
+        let proxy_client = Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .read_timeout(Duration::from_secs(30))
+            .build()
+            .expect("build http client");
+        PluginRegistry {
+            plugins: Vec::new(),
+            by_name: HashMap::new(),
+            client,
+            proxy_client,
+            manifests: Arc::new(RwLock::new(HashMap::new())),
+            breakers: Arc::new(Breakers::default()),
+            cache: Arc::new(EventCache::default()),
+            health: Arc::new(RwLock::new(HashMap::new())),
+            errors,
+            metrics,
+        }
+        .with_plugins(entries)
+    }
+
+    /// A registry for a reloaded config. Clients, cached events, route rules
+    /// and breaker state carry over, except for plugins that were removed or
+    /// whose entry changed.
+    pub fn reconfigure(&self, entries: &[PluginEntry]) -> Self {
+        let next = self.clone().with_plugins(entries);
+        for old in &self.plugins {
+            if next.get(&old.name) != Some(old) {
+                next.forget(&old.name);
+            }
+        }
+        next
+    }
+
+    fn with_plugins(mut self, entries: &[PluginEntry]) -> Self {
+        self.plugins = entries
             .iter()
             .map(|e| PluginHandle {
                 name: e.name.clone(),
                 base_url: e.url.clone(),
                 token: e.token.clone(),
+                max_body: e.max_body,
+                events_timeout: Duration::from_millis(e.events_timeout_ms),
+                manifest_timeout: Duration::from_millis(e.manifest_timeout_ms),
+                retries: e.retries,
+                retry_backoff: Duration::from_millis(e.retry_backoff_ms),
+                breaker_threshold: e.breaker_threshold,
+                breaker_cooldown: Duration::from_secs(e.breaker_cooldown_secs),
+                cache_ttl: Duration::from_secs(e.cache_ttl_secs),
+                manifest_ttl: Duration::from_secs(e.manifest_ttl_secs),
             })
-            .collect::<Vec<_>>();
-        let by_name = plugins
+            .collect();
+        self.by_name = self
+            .plugins
             .iter()
             .enumerate()
             .map(|(i, p)| (p.name.clone(), i))
             .collect();
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("build http client");
-        PluginRegistry {
-            plugins,
-            by_name,
-            client,
+        self
+    }
+
+    /// Drop all per-plugin state kept for `plugin`.
+    fn forget(&self, plugin: &str) {
+        if let Ok(mut manifests) = self.manifests.write() {
+            manifests.remove(plugin);
+        }
+        self.breakers.record_success(plugin);
+        self.cache.forget(plugin);
+        if let Ok(mut health) = self.health.write() {
+            health.remove(plugin);
         }
+        self.metrics.forget(plugin);
     }
 
     pub fn all(&self) -> &[PluginHandle] {
@@ -72,117 +226,963 @@ impl PluginRegistry {
         self.by_name.get(name).and_then(|&i| self.plugins.get(i))
     }
 
+    /// The plugin whose shared token is `token`, for plugin → server calls.
+    /// Digests are compared rather than the tokens, as in
+    /// [`TokenStore::lookup`](crate::tokens::TokenStore::lookup), so the time
+    /// taken says nothing about how much of a guess was right.
+    pub fn by_token(&self, token: &str) -> Option<&PluginHandle> {
+        let hash = tokens::hash_secret(token);
+        self.plugins
+            .iter()
+            .find(|p| tokens::hash_secret(&p.token) == hash)
+    }
+
+    /// Drop cached `/events` results of `plugin` overlapping `range`.
+    pub fn invalidate(&self, plugin: &str, range: &TimeRange) -> usize {
+        self.cache.invalidate(plugin, range)
+    }
+
+    /// Log a failure involving `plugin` and add it to the error log.
+    pub fn record_error(&self, plugin: &str, message: impl fmt::Display) {
+        let message = message.to_string();
+        tracing::warn!(plugin = %plugin, "{}", message);
+        self.errors.record(plugin, &message, request_id::current());
+    }
+
+    /// Record a failed call to `plugin` and turn it into the outcome
+    /// reported to the frontend.
+    fn failure_outcome(&self, plugin: &str, what: &str, e: FetchError) -> PluginOutcome {
+        match e {
+            FetchError::Timeout => {
+                self.record_error(plugin, format!("{} timed out", what));
+                PluginOutcome::Timeout
+            }
+            FetchError::Degraded => PluginOutcome::Degraded,
+            FetchError::Unavailable(e) | FetchError::Failed(e) => {
+                self.record_error(plugin, format!("{} failed: {}", what, e));
+                PluginOutcome::Error(e.to_string())
+            }
+        }
+    }
+
+    /// Count a proxied call to `plugin` by the status it answered with.
+    pub fn count_proxy_response(&self, plugin: &str, status: &str) {
+        self.metrics
+            .proxy_responses
+            .with_label_values(&[plugin, status])
+            .inc();
+    }
+
     pub fn client(&self) -> &Client {
         &self.client
     }
 
-    /// Fan-out `/events` to every plugin in parallel. Returns per-plugin
-    /// event lists; plugins that fail are omitted and the error is logged.
+    pub fn proxy_client(&self) -> &Client {
+        &self.proxy_client
+    }
+
+    /// Health of every configured plugin; `Unknown` until first polled.
+    pub fn health(&self) -> HashMap<String, PluginHealth> {
+        let health = self.health.read().expect("health lock");
+        self.plugins
+            .iter()
+            .map(|p| {
+                (
+                    p.name.clone(),
+                    health.get(&p.name).cloned().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    /// Call every plugin's `/health` once and record the result. The
+    /// manifest is re-read when a plugin comes (back) up, to pick up its
+    /// version. Bypasses retries and the circuit breaker: this is what
+    /// notices a plugin is back.
+    pub async fn poll_health(&self) {
+        let checks = self.plugins.iter().map(|plugin| async move {
+            let started = Instant::now();
+            let result = self.health_for(plugin).await;
+            (plugin, result, started.elapsed())
+        });
+        for (plugin, result, elapsed) in futures::future::join_all(checks).await {
+            let previous = self
+                .health
+                .read()
+                .expect("health lock")
+                .get(&plugin.name)
+                .cloned()
+                .unwrap_or_default();
+            let mut next = previous.clone();
+            match result {
+                Ok(()) => {
+                    next.state = HealthState::Up;
+                    next.last_seen = Some(chrono::Utc::now());
+                    next.latency_ms = Some(elapsed.as_millis() as u64);
+                    next.error = None;
+                    if previous.state != HealthState::Up {
+                        if previous.state == HealthState::Down {
+                            tracing::info!(plugin = %plugin.name, "plugin is back up");
+                        }
+                        // It may have been updated while it was down.
+                        if let Ok(m) = self.refresh_manifest(plugin, false).await {
+                            next.version = m.version;
+                        }
+                    }
+                }
+                Err(e) => {
+                    if previous.state != HealthState::Down {
+                        tracing::warn!(plugin = %plugin.name, "plugin is down: {}", e);
+                    }
+                    next.state = HealthState::Down;
+                    next.error = Some(e.to_string());
+                }
+            }
+            // A reload may have dropped the plugin while we were polling.
+            if self.get(&plugin.name) == Some(plugin) {
+                self.metrics
+                    .plugin_up
+                    .with_label_values(&[&plugin.name])
+                    .set((next.state == HealthState::Up).into());
+                if let Ok(mut health) = self.health.write() {
+                    health.insert(plugin.name.clone(), next);
+                }
+            }
+        }
+    }
+
+    /// Poll health forever, every `health_interval_secs` of the live config.
+    /// Both are re-read each round so reloads apply.
+    pub async fn watch_health(registry: Live<Self>, config: Live<Config>) {
+        loop {
+            let interval = config.load().health_interval_secs;
+            if interval == 0 {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+            registry.load().poll_health().await;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    }
+
+    pub async fn health_for(&self, plugin: &PluginHandle) -> Result<(), FetchError> {
+        let url = plugin
+            .base_url
+            .join("health")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
+        let res = self
+            .client
+            .get(url)
+            .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.manifest_timeout)
+            .send()
+            .await?;
+        let status = res.status();
+        if !status.is_success() {
+            let e = APIError::PluginError(format!("{} health returned {}", plugin.name, status));
+            return Err(FetchError::from_status(status, e));
+        }
+        Ok(())
+    }
+
+    /// Fan-out `/events` to every plugin in parallel (or only those in
+    /// `only`, if given), answering from the event cache where possible.
+    /// Failed plugins are logged and reported in
+    /// `EventsResponse::plugins` rather than dropped silently.
     pub async fn fan_out_events(
         &self,
         range: &TimeRange,
-    ) -> HashMap<String, Vec<CompressedEvent>> {
+        only: Option<&HashSet<String>>,
+    ) -> EventsResponse {
         use futures::stream::{FuturesUnordered, StreamExt};
         let me = Arc::new(self.clone());
         let mut futs = FuturesUnordered::new();
         for plugin in self.plugins.iter().cloned() {
+            if only.is_some_and(|o| !o.contains(&plugin.name)) {
+                continue;
+            }
             let me = me.clone();
             let range = range.clone();
             futs.push(async move {
-                let name = plugin.name.clone();
-                let result = me.events_for(&plugin, &range).await;
-                (name, result)
+                let started = Instant::now();
+                let result = me.cached_events(&plugin, &range).await;
+                (plugin.name.clone(), result, started.elapsed())
             });
         }
-        let mut out: HashMap<String, Vec<CompressedEvent>> = HashMap::new();
-        while let Some((name, result)) = futs.next().await {
-            match result {
+        let mut out = EventsResponse::default();
+        while let Some((name, result, elapsed)) = futs.next().await {
+            let outcome = match result {
                 Ok(events) => {
-                    out.insert(name, events);
+                    out.events
+                        .insert(name.clone(), Arc::unwrap_or_clone(events));
+                    PluginOutcome::Ok
                 }
-                Err(e) => {
-                    tracing::warn!(plugin = %name, "events fetch failed: {}", e);
-                }
-            }
+                Err(e) => self.failure_outcome(&name, "events fetch", e),
+            };
+            let status = PluginStatus {
+                outcome,
+                latency_ms: elapsed.as_millis() as u64,
+            };
+            out.plugins.insert(name, status);
         }
         out
     }
 
+    /// `plugin`'s events in `range`, from the event cache or fetched.
+    async fn cached_events(
+        self: &Arc<Self>,
+        plugin: &PluginHandle,
+        range: &TimeRange,
+    ) -> Result<Arc<Vec<CompressedEvent>>, FetchError> {
+        let fetch = {
+            let me = self.clone();
+            let plugin = plugin.clone();
+            let range = range.clone();
+            move || {
+                async move {
+                    me.call(&plugin, "events", || me.events_for(&plugin, &range))
+                        .await
+                        .map(Arc::new)
+                }
+                .boxed()
+            }
+        };
+        self.cache
+            .get_or_fetch(&plugin.name, range, plugin.cache_ttl, fetch)
+            .await
+    }
+
     async fn events_for(
         &self,
         plugin: &PluginHandle,
         range: &TimeRange,
-    ) -> APIResult<Vec<CompressedEvent>> {
-        let url = plugin.base_url.join("events").map_err(|e| {
-            APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e))
-        })?;
+    ) -> Result<Vec<CompressedEvent>, FetchError> {
+        let url = plugin
+            .base_url
+            .join("events")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
         let res = self
             .client
             .post(url)
             .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.events_timeout)
             .json(range)
             .send()
-            .await
-            .map_err(|e| APIError::RequestError(e.to_string()))?;
+            .await?;
         let status = res.status();
-        let text = res
-            .text()
+        let text = res.text().await?;
+        if !status.is_success() {
+            let e = APIError::PluginError(format!("{} returned {}: {}", plugin.name, status, text));
+            return Err(FetchError::from_status(status, e));
+        }
+        let result: APIResult<Vec<CompressedEvent>> =
+            serde_json::from_str(&text).map_err(APIError::from)?;
+        Ok(result?)
+    }
+
+    /// One page of events per plugin, like
+    /// [`fan_out_events`](Self::fan_out_events) but past the event cache. A
+    /// `request.cursor` (the `next` of an earlier response) says which
+    /// plugins had more and where each left off; only those are asked again.
+    pub async fn fan_out_events_page(
+        &self,
+        request: &EventsRequest,
+        only: Option<&HashSet<String>>,
+    ) -> Result<EventsResponse, APIError> {
+        use futures::stream::{FuturesUnordered, StreamExt};
+        let cursors = request.cursor.as_deref().map(decode_cursor).transpose()?;
+        let mut futs = FuturesUnordered::new();
+        for plugin in &self.plugins {
+            if only.is_some_and(|o| !o.contains(&plugin.name)) {
+                continue;
+            }
+            let cursor = match &cursors {
+                Some(c) => match c.get(&plugin.name) {
+                    Some(cursor) => Some(cursor.clone()),
+                    None => continue,
+                },
+                None => None,
+            };
+            let request = EventsRequest {
+                cursor,
+                ..request.clone()
+            };
+            futs.push(async move {
+                let started = Instant::now();
+                let result = self
+                    .call(plugin, "events", || self.page_for(plugin, &request))
+                    .await;
+                (plugin.name.clone(), result, started.elapsed())
+            });
+        }
+        let mut out = EventsResponse::default();
+        let mut next = BTreeMap::new();
+        while let Some((name, result, elapsed)) = futs.next().await {
+            let outcome = match result {
+                Ok(page) => {
+                    out.events.insert(name.clone(), page.events);
+                    if let Some(cursor) = page.next {
+                        next.insert(name.clone(), cursor);
+                    }
+                    PluginOutcome::Ok
+                }
+                Err(e) => self.failure_outcome(&name, "events fetch", e),
+            };
+            let status = PluginStatus {
+                outcome,
+                latency_ms: elapsed.as_millis() as u64,
+            };
+            out.plugins.insert(name, status);
+        }
+        out.next = (!next.is_empty()).then(|| encode_cursor(&next));
+        Ok(out)
+    }
+
+    /// One page of `plugin`'s events, for callers walking a range page by
+    /// page.
+    pub async fn events_page(
+        &self,
+        plugin: &PluginHandle,
+        request: &EventsRequest,
+    ) -> Result<EventsPage, FetchError> {
+        self.call(plugin, "events", || self.page_for(plugin, request))
             .await
-            .map_err(|e| APIError::RequestError(e.to_string()))?;
+    }
+
+    pub async fn page_for(
+        &self,
+        plugin: &PluginHandle,
+        request: &EventsRequest,
+    ) -> Result<EventsPage, FetchError> {
+        let url = plugin
+            .base_url
+            .join("events")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
+        let res = self
+            .client
+            .post(url)
+            .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.events_timeout)
+            .json(request)
+            .send()
+            .await?;
+        let status = res.status();
+        let text = res.text().await?;
         if !status.is_success() {
-            return Err(APIError::PluginError(format!(
-                "{} returned {}: {}",
-                plugin.name, status, text
-            )));
+            let e = APIError::PluginError(format!("{} returned {}: {}", plugin.name, status, text));
+            return Err(FetchError::from_status(status, e));
         }
-        serde_json::from_str::<APIResult<Vec<CompressedEvent>>>(&text)?
+        let result: APIResult<RemoteEvents> =
+            serde_json::from_str(&text).map_err(APIError::from)?;
+        Ok(match result? {
+            RemoteEvents::Page(page) => page,
+            // Built with an SDK from before paging: that's everything.
+            RemoteEvents::All(events) => EventsPage { events, next: None },
+        })
     }
 
-    /// Collect the manifest shapes each plugin advertises.
-    pub async fn fan_out_manifests(&self) -> Vec<RemoteManifest> {
+    /// Markers for each plugin (or only those in `only`), counted by the
+    /// plugins themselves. Plugins without `/markers` (by their manifest, or
+    /// answering 404) have their events fetched (or taken from the cache) and
+    /// counted here. Failed plugins are logged and left out.
+    pub async fn fan_out_markers(
+        &self,
+        request: &MarkersRequest,
+        only: Option<&HashSet<String>>,
+    ) -> HashMap<String, Vec<Marker>> {
         use futures::stream::{FuturesUnordered, StreamExt};
         let me = Arc::new(self.clone());
         let mut futs = FuturesUnordered::new();
         for plugin in self.plugins.iter().cloned() {
+            if only.is_some_and(|o| !o.contains(&plugin.name)) {
+                continue;
+            }
             let me = me.clone();
             futs.push(async move {
-                let result = me.manifest_for(&plugin).await;
+                let counted = if me.supports(&plugin.name, Capability::Markers) {
+                    me.call(&plugin, "markers", || me.markers_for(&plugin, request))
+                        .await
+                } else {
+                    Ok(None)
+                };
+                let result = match counted {
+                    Ok(Some(markers)) => Ok(markers),
+                    Ok(None) => me
+                        .cached_events(&plugin, &request.range)
+                        .await
+                        .map(|events| markers::count(&events, request)),
+                    Err(e) => Err(e),
+                };
                 (plugin.name.clone(), result)
             });
         }
-        let mut out = Vec::new();
+        let mut out = HashMap::new();
         while let Some((name, result)) = futs.next().await {
             match result {
-                Ok(m) => out.push(m),
-                Err(e) => tracing::warn!(plugin = %name, "manifest fetch failed: {}", e),
+                Ok(markers) => {
+                    out.insert(name, markers);
+                }
+                Err(FetchError::Degraded) => {}
+                Err(e) => self.record_error(&name, format!("markers failed: {}", e)),
             }
         }
-        out.sort_by(|a, b| a.name.cmp(&b.name));
         out
     }
 
-    async fn manifest_for(&self, plugin: &PluginHandle) -> APIResult<RemoteManifest> {
-        let url = plugin.base_url.join("manifest").map_err(|e| {
-            APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e))
-        })?;
+    /// `None` if the plugin has no `/markers` (an older SDK, or events that
+    /// don't come from its Db); its events have to be counted instead.
+    async fn markers_for(
+        &self,
+        plugin: &PluginHandle,
+        request: &MarkersRequest,
+    ) -> Result<Option<Vec<Marker>>, FetchError> {
+        let url = plugin
+            .base_url
+            .join("markers")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
         let res = self
             .client
-            .get(url)
+            .post(url)
             .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.events_timeout)
+            .json(request)
             .send()
-            .await
-            .map_err(|e| APIError::RequestError(e.to_string()))?;
+            .await?;
+        let status = res.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let text = res.text().await?;
+        if !status.is_success() {
+            let e = APIError::PluginError(format!("{} returned {}: {}", plugin.name, status, text));
+            return Err(FetchError::from_status(status, e));
+        }
+        let result: APIResult<Vec<Marker>> = serde_json::from_str(&text).map_err(APIError::from)?;
+        Ok(Some(result?))
+    }
+
+    /// Fan-out `/search` like [`fan_out_events`](Self::fan_out_events), minus
+    /// the cache. Plugins whose manifest doesn't list `search` aren't asked.
+    pub async fn fan_out_search(
+        &self,
+        request: &SearchRequest,
+        only: Option<&HashSet<String>>,
+    ) -> SearchResponse {
+        use futures::stream::{FuturesUnordered, StreamExt};
+        let mut futs = FuturesUnordered::new();
+        for plugin in &self.plugins {
+            if only.is_some_and(|o| !o.contains(&plugin.name))
+                || !self.supports(&plugin.name, Capability::Search)
+            {
+                continue;
+            }
+            futs.push(async move {
+                let started = Instant::now();
+                let result = self
+                    .call(plugin, "search", || self.search_for(plugin, request))
+                    .await;
+                (plugin.name.clone(), result, started.elapsed())
+            });
+        }
+        let mut out = SearchResponse::default();
+        while let Some((name, result, elapsed)) = futs.next().await {
+            let outcome = match result {
+                Ok(events) => {
+                    out.results.insert(name.clone(), events);
+                    PluginOutcome::Ok
+                }
+                Err(e) => self.failure_outcome(&name, "search", e),
+            };
+            let status = PluginStatus {
+                outcome,
+                latency_ms: elapsed.as_millis() as u64,
+            };
+            out.plugins.insert(name, status);
+        }
+        out
+    }
+
+    async fn search_for(
+        &self,
+        plugin: &PluginHandle,
+        request: &SearchRequest,
+    ) -> Result<Vec<CompressedEvent>, FetchError> {
+        let url = plugin
+            .base_url
+            .join("search")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
+        let res = self
+            .client
+            .post(url)
+            .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.events_timeout)
+            .json(request)
+            .send()
+            .await?;
         let status = res.status();
-        let text = res
-            .text()
+        // Built with an SDK from before `/search`: nothing to find.
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let text = res.text().await?;
+        if !status.is_success() {
+            let e = APIError::PluginError(format!("{} returned {}: {}", plugin.name, status, text));
+            return Err(FetchError::from_status(status, e));
+        }
+        let result: APIResult<Vec<CompressedEvent>> =
+            serde_json::from_str(&text).map_err(APIError::from)?;
+        Ok(result?)
+    }
+
+    /// Every plugin's manifest, mostly from memory (see
+    /// [`manifest`](Self::manifest)). Plugins whose manifest can't be had
+    /// are left out.
+    pub async fn fan_out_manifests(&self) -> Vec<RemoteManifest> {
+        let fetches = self.plugins.iter().map(|plugin| self.manifest(plugin));
+        let mut out: Vec<_> = futures::future::join_all(fetches)
             .await
-            .map_err(|e| APIError::RequestError(e.to_string()))?;
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect();
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        out
+    }
+
+    /// Access rule covering `path` (relative to the plugin's base URL) for a
+    /// caller without a session. `None` means the route is private, as is
+    /// every route of a plugin whose manifest can't be had.
+    pub async fn route_access(&self, plugin: &PluginHandle, path: &str) -> Option<RouteAccess> {
+        match self.manifest(plugin).await {
+            Ok(m) => match_rule(&m.routes, path),
+            Err(_) => None,
+        }
+    }
+
+    /// `plugin`'s manifest: the cached one while it's younger than the
+    /// plugin's `manifest_ttl`, otherwise revalidated.
+    pub async fn manifest(&self, plugin: &PluginHandle) -> Result<RemoteManifest, FetchError> {
+        let cached = self.cached_manifest(&plugin.name);
+        match cached {
+            Some(c) if c.checked.elapsed() < plugin.manifest_ttl => c.result(),
+            _ => self.refresh_manifest(plugin, true).await,
+        }
+    }
+
+    fn cached_manifest(&self, plugin: &str) -> Option<CachedManifest> {
+        self.manifests.read().ok()?.get(plugin).cloned()
+    }
+
+    /// Whether `plugin` is worth calling for `capability`, going by its
+    /// cached manifest. Without one it's tried, like a plugin predating
+    /// capabilities.
+    fn supports(&self, plugin: &str, capability: Capability) -> bool {
+        self.manifests
+            .read()
+            .ok()
+            .and_then(|m| m.get(plugin).map(|c| c.manifest.supports(capability)))
+            .unwrap_or(true)
+    }
+
+    /// Fetch `plugin`'s manifest again (conditionally, if one is cached) and
+    /// check it. `guarded` goes through retries and the circuit breaker.
+    /// Failures are recorded; if the fetch fails, an earlier accepted
+    /// manifest keeps being served.
+    async fn refresh_manifest(
+        &self,
+        plugin: &PluginHandle,
+        guarded: bool,
+    ) -> Result<RemoteManifest, FetchError> {
+        let cached = self.cached_manifest(&plugin.name);
+        let etag = cached.as_ref().and_then(|c| c.etag.clone());
+        let fetch = || self.manifest_for(plugin, etag.as_deref());
+        let fetched = if guarded {
+            self.call(plugin, "manifest", fetch).await
+        } else {
+            fetch().await
+        };
+        let entry = match (fetched, cached) {
+            (Ok(ManifestFetch::Changed { manifest, etag }), _) => {
+                let rejected = verify_manifest(plugin, &manifest);
+                if let Some(why) = &rejected {
+                    self.record_error(&plugin.name, format!("manifest rejected: {}", why));
+                }
+                CachedManifest {
+                    manifest: *manifest,
+                    etag,
+                    checked: Instant::now(),
+                    rejected,
+                }
+            }
+            (Ok(ManifestFetch::Unchanged), Some(cached)) => CachedManifest {
+                checked: Instant::now(),
+                ..cached
+            },
+            (Ok(ManifestFetch::Unchanged), None) => {
+                let e = APIError::PluginError("manifest answered 304 to a plain GET".to_string());
+                self.record_error(&plugin.name, &e);
+                return Err(e.into());
+            }
+            (Err(e), cached) => {
+                if !matches!(e, FetchError::Degraded) {
+                    self.record_error(&plugin.name, format!("manifest fetch failed: {}", e));
+                }
+                return match cached {
+                    Some(c) if c.rejected.is_none() => Ok(c.manifest),
+                    _ => Err(e),
+                };
+            }
+        };
+        // A reload may have dropped or changed the plugin meanwhile.
+        if self.get(&plugin.name) == Some(plugin) {
+            if let Ok(mut manifests) = self.manifests.write() {
+                manifests.insert(plugin.name.clone(), entry.clone());
+            }
+            if let Ok(mut health) = self.health.write() {
+                if let Some(h) = health.get_mut(&plugin.name) {
+                    h.version = entry.manifest.version.clone();
+                }
+            }
+        }
+        entry.result()
+    }
+
+    async fn manifest_for(
+        &self,
+        plugin: &PluginHandle,
+        etag: Option<&str>,
+    ) -> Result<ManifestFetch, FetchError> {
+        let url = plugin
+            .base_url
+            .join("manifest")
+            .map_err(|e| APIError::Custom(format!("plugin {} bad url: {}", plugin.name, e)))?;
+        let mut request = self
+            .client
+            .get(url)
+            .bearer_auth(&plugin.token)
+            .with_request_id()
+            .timeout(plugin.manifest_timeout);
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let res = request.send().await?;
+        let status = res.status();
+        if status == reqwest::StatusCode::NOT_MODIFIED && etag.is_some() {
+            return Ok(ManifestFetch::Unchanged);
+        }
+        let etag = res
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let text = res.text().await?;
         if !status.is_success() {
-            return Err(APIError::PluginError(format!(
-                "{} manifest returned {}",
-                plugin.name, status
-            )));
+            let e = APIError::PluginError(format!("{} manifest returned {}", plugin.name, status));
+            return Err(FetchError::from_status(status, e));
+        }
+        let manifest = serde_json::from_str(&text).map_err(APIError::from)?;
+        Ok(ManifestFetch::Changed { manifest, etag })
+    }
+
+    /// Run `attempt` under the plugin's retry policy, behind its circuit
+    /// breaker. `what` labels the call in the metrics.
+    async fn call<T, F, Fut>(
+        &self,
+        plugin: &PluginHandle,
+        what: &'static str,
+        mut attempt: F,
+    ) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        if self.breakers.is_open(&plugin.name) {
+            self.count_error(plugin, what, &FetchError::Degraded);
+            return Err(FetchError::Degraded);
         }
-        Ok(serde_json::from_str::<RemoteManifest>(&text)?)
+        let started = Instant::now();
+        let mut retries_left = plugin.retries;
+        let mut backoff = plugin.retry_backoff;
+        let result = loop {
+            match attempt().await {
+                Err(e) if e.retryable() && retries_left > 0 => {
+                    tracing::debug!(plugin = %plugin.name, "retrying in {:?}: {}", backoff, e);
+                    retries_left -= 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => break result,
+            }
+        };
+        self.metrics
+            .plugin_call_seconds
+            .with_label_values(&[&plugin.name, what])
+            .observe(started.elapsed().as_secs_f64());
+        match &result {
+            Ok(_) => self.breakers.record_success(&plugin.name),
+            Err(e) => {
+                self.count_error(plugin, what, e);
+                self.breakers.record_failure(
+                    &plugin.name,
+                    plugin.breaker_threshold,
+                    plugin.breaker_cooldown,
+                )
+            }
+        }
+        result
+    }
+
+    fn count_error(&self, plugin: &PluginHandle, what: &str, e: &FetchError) {
+        let reason = match e {
+            FetchError::Timeout => "timeout",
+            FetchError::Unavailable(_) => "unavailable",
+            FetchError::Failed(_) => "failed",
+            FetchError::Degraded => "degraded",
+        };
+        self.metrics
+            .plugin_call_errors
+            .with_label_values(&[&plugin.name, what, reason])
+            .inc();
+    }
+}
+
+/// Mirror of the SDK's `EventsReply`: a plugin's `/events` answer.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RemoteEvents {
+    Page(EventsPage),
+    All(Vec<CompressedEvent>),
+}
+
+/// `EventsResponse::next`: each plugin's own cursor, by plugin name.
+fn encode_cursor(cursors: &BTreeMap<String, String>) -> String {
+    hex::encode(serde_json::to_vec(cursors).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Result<BTreeMap<String, String>, APIError> {
+    hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| APIError::Custom("invalid cursor".to_string()))
+}
+
+/// Why `manifest` can't be used for `plugin`, if it can't.
+fn verify_manifest(plugin: &PluginHandle, manifest: &RemoteManifest) -> Option<String> {
+    if manifest.name != plugin.name {
+        return Some(format!(
+            "the plugin calls itself {:?}, but is configured as {:?}",
+            manifest.name, plugin.name
+        ));
+    }
+    match manifest.protocol_version {
+        Some(v) if v != PLUGIN_PROTOCOL_VERSION => Some(format!(
+            "the plugin speaks protocol version {}, this server {}",
+            v, PLUGIN_PROTOCOL_VERSION
+        )),
+        _ => None,
+    }
+}
+
+/// The first rule whose prefix covers `path` in whole segments: `file`
+/// covers `file` and `file/...` but not `filesecret/...`.
+fn match_rule(rules: &[RouteRule], path: &str) -> Option<RouteAccess> {
+    let path = path.trim_start_matches('/');
+    rules
+        .iter()
+        .find(|r| {
+            let prefix = r.prefix.trim_matches('/');
+            prefix.is_empty()
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .map(|r| r.access)
+}
+
+/// Why a call to a plugin failed.
+#[derive(Debug, Clone)]
+pub enum FetchError {
+    Timeout,
+    /// Connection errors and 5xx responses; worth retrying.
+    Unavailable(APIError),
+    Failed(APIError),
+    /// Skipped: the plugin's circuit breaker is open.
+    Degraded,
+}
+
+impl FetchError {
+    fn from_status(status: reqwest::StatusCode, e: APIError) -> Self {
+        if status.is_server_error() {
+            Self::Unavailable(e)
+        } else {
+            Self::Failed(e)
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Unavailable(_))
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timed out"),
+            Self::Unavailable(e) | Self::Failed(e) => write!(f, "{}", e),
+            Self::Degraded => write!(f, "skipped, circuit open"),
+        }
+    }
+}
+
+impl From<APIError> for FetchError {
+    fn from(e: APIError) -> Self {
+        Self::Failed(e)
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() {
+            Self::Unavailable(APIError::RequestError(e.to_string()))
+        } else {
+            Self::Failed(APIError::RequestError(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::{TimeZone, Utc};
+    use rocket::serde::json::Json;
+    use rocket::{get, post, routes, Route};
+    use types::timing::{MarkerResolution, Timing};
+
+    use super::*;
+
+    pub fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .unwrap()
+            .port()
+    }
+
+    /// Wait for something to listen on `port`, and return its URL.
+    pub async fn listening(port: u16) -> url::Url {
+        while tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        format!("http://127.0.0.1:{}/", port).parse().unwrap()
+    }
+
+    /// Serve `routes` on a free local port, as a stand-in plugin.
+    pub async fn spawn_plugin(routes: Vec<Route>) -> url::Url {
+        let port = free_port();
+        let figment = rocket::Config::figment()
+            .merge(("port", port))
+            .merge(("log_level", "off"));
+        tokio::spawn(rocket::custom(figment).mount("/", routes).launch());
+        listening(port).await
+    }
+
+    /// A registry with one `[[plugin]]` entry per `(name, url)`.
+    pub fn registry(plugins: &[(&str, &url::Url)]) -> PluginRegistry {
+        let entries: Vec<PluginEntry> = plugins
+            .iter()
+            .map(|(name, url)| {
+                toml::from_str(&format!(
+                    "name = {:?}\nurl = {:?}\ntoken = \"t\"",
+                    name,
+                    url.as_str()
+                ))
+                .unwrap()
+            })
+            .collect();
+        PluginRegistry::new(
+            &entries,
+            Arc::new(ErrorLog::in_memory()),
+            Arc::new(Metrics::new()),
+        )
+    }
+
+    fn at(hour: u32) -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, hour, 0, 0).unwrap()
+    }
+
+    #[get("/manifest")]
+    fn events_only_manifest() -> Json<RemoteManifest> {
+        Json(RemoteManifest {
+            name: "in_memory".to_string(),
+            display_name: "In memory".to_string(),
+            protocol_version: Some(PLUGIN_PROTOCOL_VERSION),
+            capabilities: Some(vec![Capability::EventsPaging]),
+            ..Default::default()
+        })
+    }
+
+    #[post("/events")]
+    fn two_events() -> Json<APIResult<Vec<CompressedEvent>>> {
+        let event = |hour| CompressedEvent {
+            data: serde_json::Value::Null,
+            time: Timing::Instant(at(hour)),
+            title: "event".to_string(),
+        };
+        Json(Ok(vec![event(1), event(3)]))
+    }
+
+    /// What an SDK plugin without Db events used to answer: nothing.
+    #[post("/markers")]
+    fn no_markers() -> Json<APIResult<Vec<Marker>>> {
+        Json(Ok(Vec::new()))
+    }
+
+    #[test]
+    fn route_rules_match_whole_segments() {
+        let rules = [RouteRule {
+            prefix: "file/".to_string(),
+            access: RouteAccess::Signed,
+        }];
+        assert_eq!(match_rule(&rules, "/file"), Some(RouteAccess::Signed));
+        assert_eq!(match_rule(&rules, "/file/a.pdf"), Some(RouteAccess::Signed));
+        assert_eq!(match_rule(&rules, "/filesecret/a.pdf"), None);
+        assert_eq!(match_rule(&rules, "/files_admin"), None);
+    }
+
+    #[test]
+    fn plugins_are_found_by_their_whole_token_only() {
+        let url = url::Url::parse("http://127.0.0.1:1/").unwrap();
+        let registry = registry(&[("a", &url)]);
+        assert_eq!(registry.by_token("t").map(|p| p.name.as_str()), Some("a"));
+        assert!(registry.by_token("").is_none());
+        assert!(registry.by_token("tt").is_none());
+    }
+
+    #[rocket::async_test]
+    async fn markers_are_counted_here_without_the_capability() {
+        let url = spawn_plugin(routes![events_only_manifest, two_events, no_markers]).await;
+        let registry = registry(&[("in_memory", &url)]);
+        assert_eq!(registry.fan_out_manifests().await.len(), 1);
+
+        let request = MarkersRequest {
+            range: TimeRange {
+                start: at(0),
+                end: at(6),
+            },
+            resolution: MarkerResolution::Hour,
+        };
+        let markers = registry.fan_out_markers(&request, None).await;
+        let counted: Vec<_> = markers["in_memory"]
+            .iter()
+            .map(|m| (m.time, m.amount))
+            .collect();
+        assert_eq!(counted, vec![(at(1), 1), (at(3), 1)]);
     }
 }
@@ -1,18 +1,52 @@
 //! Forward `/api/plugin/<name>/<path..>` requests to the plugin process,
 //! adding the bearer token the plugin expects.
+//!
+//! Callers need the same login as the rest of `/api`, except for paths the
+//! plugin marks public or signed-URL-only in its manifest (`routes`).
+//!
+//! Bodies are streamed in both directions, and the headers needed for range
+//! requests, revalidation and downloads are passed through.
 
+use std::io;
+
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
 use reqwest::Method;
 use rocket::data::{ByteUnit, Data};
 use rocket::http::uri::fmt::Path as UriPath;
 use rocket::http::uri::Segments;
-use rocket::http::{ContentType, Status};
+use rocket::http::{Header, Status};
 use rocket::request::{FromRequest, Outcome};
 use rocket::{delete, get, post, put, response, Request, State};
+use tokio::io::AsyncReadExt;
+use tokio_util::io::StreamReader;
+
+use crate::auth::{Access, Caller, Grant};
+use crate::plugin_registry::{PluginRegistry, RouteAccess};
+use crate::reload::Live;
+use crate::request_id::WithRequestId;
 
-use crate::plugin_registry::PluginRegistry;
+/// Request headers passed on to the plugin.
+const FORWARD_REQUEST_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "range",
+    "if-range",
+    "if-none-match",
+    "if-modified-since",
+];
 
-/// 8 MiB should comfortably cover plugin bodies (event registration, etc).
-const MAX_BODY_BYTES: ByteUnit = ByteUnit::Mebibyte(8);
+/// Response headers passed back from the plugin.
+const FORWARD_RESPONSE_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "content-range",
+    "accept-ranges",
+    "etag",
+    "last-modified",
+    "cache-control",
+    "content-disposition",
+];
 
 /// The raw, still-percent-encoded path + query of the incoming request.
 ///
@@ -23,36 +57,47 @@ const MAX_BODY_BYTES: ByteUnit = ByteUnit::Mebibyte(8);
 /// decoded `/`, so those requests never match and fall through to the SPA 404
 /// catcher. Even if they matched, decoding would corrupt the bytes the plugin
 /// signed over. So we forward the raw tail verbatim.
-struct RawTail {
+pub struct RawTail {
     path: String,
     query: Option<String>,
+    /// The subset of request headers in `FORWARD_REQUEST_HEADERS`.
+    headers: Vec<(&'static str, String)>,
 }
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for RawTail {
     type Error = std::convert::Infallible;
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = FORWARD_REQUEST_HEADERS
+            .iter()
+            .filter_map(|&h| req.headers().get_one(h).map(|v| (h, v.to_string())))
+            .collect();
         Outcome::Success(RawTail {
             path: req.uri().path().as_str().to_string(),
             query: req.uri().query().map(|q| q.as_str().to_string()),
+            headers,
         })
     }
 }
 
 pub struct ProxiedResponse {
     status: Status,
-    content_type: Option<ContentType>,
-    body: Vec<u8>,
+    headers: Vec<Header<'static>>,
+    body: reqwest::Response,
 }
 
 impl<'r> response::Responder<'r, 'static> for ProxiedResponse {
     fn respond_to(self, _req: &'r rocket::Request<'_>) -> response::Result<'static> {
         let mut builder = response::Response::build();
         builder.status(self.status);
-        if let Some(ct) = self.content_type {
-            builder.header(ct);
+        for header in self.headers {
+            builder.header(header);
         }
-        builder.sized_body(self.body.len(), std::io::Cursor::new(self.body));
+        let stream = self
+            .body
+            .bytes_stream()
+            .map(|r| r.map_err(io::Error::other));
+        builder.streamed_body(StreamReader::new(stream));
         builder.ok()
     }
 }
@@ -64,10 +109,11 @@ pub async fn proxy_get(
     name: &str,
     path: Segments<'_, UriPath>,
     raw: RawTail,
-    registry: &State<PluginRegistry>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> Result<ProxiedResponse, Status> {
     let _ = path;
-    proxy(registry, name, raw, Method::GET, None).await
+    proxy(&registry.load(), name, raw, &caller, Method::GET, None).await
 }
 
 #[post("/plugin/<name>/<path..>", data = "<data>")]
@@ -76,16 +122,19 @@ pub async fn proxy_post(
     path: Segments<'_, UriPath>,
     raw: RawTail,
     data: Data<'_>,
-    registry: &State<PluginRegistry>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> Result<ProxiedResponse, Status> {
     let _ = path;
-    let body = data
-        .open(MAX_BODY_BYTES)
-        .into_bytes()
-        .await
-        .map_err(|_| Status::PayloadTooLarge)?
-        .value;
-    proxy(registry, name, raw, Method::POST, Some(body)).await
+    proxy(
+        &registry.load(),
+        name,
+        raw,
+        &caller,
+        Method::POST,
+        Some(data),
+    )
+    .await
 }
 
 #[put("/plugin/<name>/<path..>", data = "<data>")]
@@ -94,16 +143,19 @@ pub async fn proxy_put(
     path: Segments<'_, UriPath>,
     raw: RawTail,
     data: Data<'_>,
-    registry: &State<PluginRegistry>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> Result<ProxiedResponse, Status> {
     let _ = path;
-    let body = data
-        .open(MAX_BODY_BYTES)
-        .into_bytes()
-        .await
-        .map_err(|_| Status::PayloadTooLarge)?
-        .value;
-    proxy(registry, name, raw, Method::PUT, Some(body)).await
+    proxy(
+        &registry.load(),
+        name,
+        raw,
+        &caller,
+        Method::PUT,
+        Some(data),
+    )
+    .await
 }
 
 #[delete("/plugin/<name>/<path..>")]
@@ -111,60 +163,255 @@ pub async fn proxy_delete(
     name: &str,
     path: Segments<'_, UriPath>,
     raw: RawTail,
-    registry: &State<PluginRegistry>,
+    caller: Caller,
+    registry: &State<Live<PluginRegistry>>,
 ) -> Result<ProxiedResponse, Status> {
     let _ = path;
-    proxy(registry, name, raw, Method::DELETE, None).await
+    proxy(&registry.load(), name, raw, &caller, Method::DELETE, None).await
 }
 
 async fn proxy(
     registry: &PluginRegistry,
     name: &str,
     raw: RawTail,
+    caller: &Caller,
     method: Method,
-    body: Option<Vec<u8>>,
+    body: Option<Data<'_>>,
 ) -> Result<ProxiedResponse, Status> {
-    let plugin = registry.get(name).ok_or(Status::NotFound)?;
+    let grant = caller
+        .require(Access::Proxy {
+            plugin: name,
+            write: method != Method::GET,
+        })
+        .ok();
+    // Don't reveal which plugin names exist to anonymous callers.
+    let plugin = match registry.get(name) {
+        Some(p) => p,
+        None if grant.is_some() => return Err(Status::NotFound),
+        None => return Err(Status::Unauthorized),
+    };
     // Strip the `/api/plugin/<name>/` prefix, keeping the raw encoding of the
     // tail. `Url::join`/`Url::parse` preserve existing `%XX` sequences in the
     // path, so the plugin receives the exact signed bytes back.
     let prefix = format!("/api/plugin/{}/", name);
     let tail = raw.path.strip_prefix(&prefix).unwrap_or("");
-    let mut upstream = plugin
-        .base_url
-        .join(tail)
-        .map_err(|_| Status::BadRequest)?;
+    let mut upstream = plugin.base_url.join(tail).map_err(|_| Status::BadRequest)?;
     upstream.set_query(raw.query.as_deref());
 
+    let attach_token = if let Some(grant) = grant {
+        if let Grant::Token { name: token, .. } = grant {
+            tracing::info!(plugin = %name, token = %token, "proxy {} {}", method, tail);
+        }
+        true
+    } else {
+        // Match access rules against the resolved upstream path rather than
+        // the raw tail, so `public/../private` can't borrow a public prefix.
+        let rel = upstream
+            .path()
+            .strip_prefix(plugin.base_url.path())
+            .ok_or(Status::BadRequest)?;
+        match registry.route_access(plugin, rel).await {
+            Some(RouteAccess::Public) => true,
+            Some(RouteAccess::Signed) => false,
+            None => return Err(Status::Unauthorized),
+        }
+    };
+
     let mut req = registry
-        .client()
+        .proxy_client()
         .request(method, upstream)
-        .bearer_auth(&plugin.token);
-    if let Some(body) = body {
-        req = req.body(body);
+        .with_request_id();
+    if attach_token {
+        req = req.bearer_auth(&plugin.token);
+    }
+    for (name, value) in &raw.headers {
+        req = req.header(*name, value);
     }
-    let res = match req.send().await {
+
+    let res = match body {
+        None => req.send().await,
+        Some(data) => {
+            let declared = raw
+                .headers
+                .iter()
+                .find(|(h, _)| *h == "content-length")
+                .and_then(|(_, v)| v.parse::<u64>().ok());
+            if declared.is_some_and(|len| len > plugin.max_body.as_u64()) {
+                return Err(Status::PayloadTooLarge);
+            }
+            let (tx, rx) = mpsc::channel(4);
+            let req = req.body(reqwest::Body::wrap_stream(rx));
+            let (res, upload) = futures::join!(req.send(), upload(data, plugin.max_body, tx));
+            upload?;
+            res
+        }
+    };
+    let res = match res {
         Ok(r) => r,
         Err(e) => {
-            tracing::warn!(plugin = %name, "proxy failed: {}", e);
+            registry.count_proxy_response(name, "error");
+            registry.record_error(name, format!("proxy failed: {}", e));
             return Err(Status::BadGateway);
         }
     };
+    registry.count_proxy_response(name, res.status().as_str());
     let status = Status::from_code(res.status().as_u16()).unwrap_or(Status::InternalServerError);
-    let content_type = res
-        .headers()
-        .get("content-type")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| ContentType::parse_flexible(s));
-    let body = res
-        .bytes()
-        .await
-        .map_err(|_| Status::BadGateway)?
-        .to_vec();
+    let headers = FORWARD_RESPONSE_HEADERS
+        .iter()
+        .filter_map(|&h| {
+            let value = res.headers().get(h)?.to_str().ok()?;
+            Some(Header::new(h, value.to_string()))
+        })
+        .collect();
 
     Ok(ProxiedResponse {
         status,
-        content_type,
-        body,
+        headers,
+        body: res,
     })
 }
+
+/// Copy the request body into `tx`, which feeds the upstream request. Bodies
+/// over `limit` abort the upload with 413.
+async fn upload(
+    data: Data<'_>,
+    limit: ByteUnit,
+    mut tx: mpsc::Sender<io::Result<Vec<u8>>>,
+) -> Result<(), Status> {
+    // Read one byte past the limit so an oversized body is detectable.
+    let mut stream = data.open(limit + 1);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut total = 0u64;
+    loop {
+        let n = stream
+            .read(&mut buf)
+            .await
+            .map_err(|_| Status::BadRequest)?;
+        if n == 0 {
+            return Ok(());
+        }
+        total += n as u64;
+        if total > limit.as_u64() {
+            let _ = tx.send(Err(io::Error::other("body too large"))).await;
+            return Err(Status::PayloadTooLarge);
+        }
+        if tx.send(Ok(buf[..n].to_vec())).await.is_err() {
+            // The plugin answered without reading the whole body.
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rocket::local::asynchronous::Client;
+    use rocket::routes;
+    use timeline_plugin_sdk::{
+        APIResult, CompressedEvent, Context, Manifest, Plugin, RouteRule, SignedUrl, Style,
+        TimeRange, UrlSigner,
+    };
+
+    use super::*;
+    use crate::config::Config;
+    use crate::plugin_registry::tests::{free_port, listening, registry};
+    use crate::session::SessionStore;
+    use crate::throttle::AuthThrottle;
+    use crate::tokens::TokenStore;
+
+    #[get("/file/<name>")]
+    fn file(_auth: SignedUrl, name: &str) -> String {
+        format!("contents of {}", name)
+    }
+
+    /// Serves `/file/<name>` to signed links, like documents or media_scan.
+    struct Files;
+
+    impl Plugin for Files {
+        async fn new(_ctx: Context) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                name: "files".to_string(),
+                display_name: "Files".to_string(),
+                style: Style::Acc1,
+                icon: None,
+                web_entry: None,
+            }
+        }
+
+        async fn events(&self, _range: TimeRange) -> APIResult<Vec<CompressedEvent>> {
+            Ok(Vec::new())
+        }
+
+        fn routes(&self) -> Vec<rocket::Route> {
+            routes![file]
+        }
+
+        fn route_access(&self) -> Vec<RouteRule> {
+            vec![RouteRule::signed("file")]
+        }
+    }
+
+    #[rocket::async_test]
+    async fn anonymous_signed_links_reach_the_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let port = free_port();
+        let plugin_config = dir.path().join("files.toml");
+        std::fs::write(
+            &plugin_config,
+            format!(
+                "[plugin]\nname = \"files\"\nport = {}\ntoken = \"t\"\ndata_dir = {:?}\n",
+                port,
+                dir.path().join("plugin")
+            ),
+        )
+        .unwrap();
+        tokio::spawn(timeline_plugin_sdk::launch::<Files>(plugin_config));
+        let url = listening(port).await;
+
+        let config: Config = toml::from_str(&format!(
+            "port = 0\npassword_hash = \"\"\ndata_dir = {:?}",
+            dir.path()
+        ))
+        .unwrap();
+        let config = Live::new(config);
+        let server = rocket::build()
+            .manage(Live::new(registry(&[("files", &url)])))
+            .manage(
+                SessionStore::open(dir.path().join("sessions.json"), chrono::Duration::days(1))
+                    .await
+                    .unwrap(),
+            )
+            .manage(
+                TokenStore::open(dir.path().join("tokens.json"), config.clone())
+                    .await
+                    .unwrap(),
+            )
+            .manage(AuthThrottle::new(config))
+            .mount("/api", routes![proxy_get]);
+        let client = Client::untracked(server).await.unwrap();
+        let get = |path: String| {
+            let client = &client;
+            async move {
+                let res = client.get(path).dispatch().await;
+                (res.status(), res.into_string().await)
+            }
+        };
+
+        let link = UrlSigner::new("files", "t").sign("file/a.pdf", Duration::from_secs(60));
+        assert_eq!(
+            get(link.clone()).await,
+            (Status::Ok, Some("contents of a.pdf".to_string()))
+        );
+        // Forwarded without the token, so the plugin's signature check decides.
+        let (status, _) = get(link.replace("a.pdf", "b.pdf")).await;
+        assert_eq!(status, Status::Unauthorized);
+        // Not covered by the `file` rule: refused before reaching the plugin.
+        let (status, _) = get(link.replace("/file/", "/filesecret/")).await;
+        assert_eq!(status, Status::Unauthorized);
+    }
+}
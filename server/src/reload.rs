@@ -0,0 +1,204 @@
+//! Reloading `config.toml` without a restart.
+//!
+//! The file is re-read on SIGHUP, when its mtime changes and on
+//! `/api/config/reload`. A config that doesn't validate is rejected and the
+//! running one stays. Otherwise the new `Config` and `PluginRegistry` are
+//! swapped in; requests that already hold the old ones finish with them.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::plugin_registry::PluginRegistry;
+use crate::supervisor::Supervisor;
+
+/// How often the config file's mtime is checked.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Shared state that can be replaced while requests are using it.
+#[derive(Debug)]
+pub struct Live<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> Clone for Live<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Live<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(value))))
+    }
+
+    /// The current value. Holding on to it keeps that version alive.
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().expect("live lock").clone()
+    }
+
+    fn store(&self, value: T) {
+        *self.0.write().expect("live lock") = Arc::new(value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadTrigger {
+    Signal,
+    FileChange,
+    Api,
+}
+
+/// Outcome of one reload attempt, as logged and served by
+/// `/api/config/last_reload`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadReport {
+    pub at: DateTime<Utc>,
+    pub trigger: ReloadTrigger,
+    /// Why the new config was rejected; `None` if it was applied.
+    pub error: Option<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// Changed settings that only take effect after a restart.
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Reloader {
+    path: PathBuf,
+    config: Live<Config>,
+    registry: Live<PluginRegistry>,
+    supervisor: Supervisor,
+    last: Arc<Mutex<Option<ReloadReport>>>,
+    /// One reload at a time.
+    running: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Reloader {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        config: Live<Config>,
+        registry: Live<PluginRegistry>,
+        supervisor: Supervisor,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            config,
+            registry,
+            supervisor,
+            last: Arc::new(Mutex::new(None)),
+            running: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    pub fn last(&self) -> Option<ReloadReport> {
+        self.last.lock().expect("reload lock").clone()
+    }
+
+    pub async fn reload(&self, trigger: ReloadTrigger) -> ReloadReport {
+        let _running = self.running.lock().await;
+        let mut report = ReloadReport {
+            at: Utc::now(),
+            trigger,
+            error: None,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            restart_required: Vec::new(),
+        };
+        let loaded = match Config::load(&self.path).await {
+            Ok(c) => c.validate().map(|()| c),
+            Err(e) => Err(e),
+        };
+        match loaded {
+            Err(e) => {
+                tracing::error!(?trigger, "config reload rejected: {}", e);
+                report.error = Some(e.to_string());
+            }
+            Ok(new) => {
+                let old = self.config.load();
+                diff(&old, &new, &mut report);
+                let registry = self.registry.load().reconfigure(&new.plugin);
+                self.registry.store(registry);
+                self.supervisor.apply(&new.plugin).await;
+                self.config.store(new);
+                tracing::info!(
+                    ?trigger,
+                    added = ?report.added,
+                    removed = ?report.removed,
+                    changed = ?report.changed,
+                    "config reloaded"
+                );
+                if !report.restart_required.is_empty() {
+                    tracing::warn!(
+                        settings = ?report.restart_required,
+                        "changed settings need a restart"
+                    );
+                }
+            }
+        }
+        *self.last.lock().expect("reload lock") = Some(report.clone());
+        report
+    }
+
+    /// Reload on SIGHUP and whenever the file's mtime changes. Runs forever.
+    pub async fn watch(self) {
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(s) => Some(s),
+            Err(e) => {
+                tracing::warn!("unable to listen for SIGHUP: {}", e);
+                None
+            }
+        };
+        let mut seen = self.mtime().await;
+        let mut tick = tokio::time::interval(WATCH_INTERVAL);
+        loop {
+            tokio::select! {
+                Some(()) = async { hangup.as_mut()?.recv().await } => {
+                    self.reload(ReloadTrigger::Signal).await;
+                    seen = self.mtime().await;
+                }
+                _ = tick.tick() => {
+                    let mtime = self.mtime().await;
+                    if mtime.is_some() && mtime != seen {
+                        seen = mtime;
+                        self.reload(ReloadTrigger::FileChange).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn mtime(&self) -> Option<SystemTime> {
+        tokio::fs::metadata(&self.path).await.ok()?.modified().ok()
+    }
+}
+
+fn diff(old: &Config, new: &Config, report: &mut ReloadReport) {
+    for entry in &new.plugin {
+        match old.plugin.iter().find(|p| p.name == entry.name) {
+            None => report.added.push(entry.name.clone()),
+            Some(prev) if prev != entry => report.changed.push(entry.name.clone()),
+            Some(_) => {}
+        }
+    }
+    for entry in &old.plugin {
+        if !new.plugin.iter().any(|p| p.name == entry.name) {
+            report.removed.push(entry.name.clone());
+        }
+    }
+    if old.port != new.port {
+        report.restart_required.push("port".to_string());
+    }
+    if old.data_dir != new.data_dir {
+        report.restart_required.push("data_dir".to_string());
+    }
+    if old.session_days != new.session_days {
+        report.restart_required.push("session_days".to_string());
+    }
+}
@@ -0,0 +1,99 @@
+//! `X-Request-Id` correlation. Each request's id is taken from the incoming
+//! header (or made up), echoed on the response, and sent along on every call
+//! the server makes to a plugin while handling it, so log lines on both
+//! sides can be matched up.
+//!
+//! Handlers run inside a `request{id=..}` tracing span with the id in a
+//! task-local, which [`WithRequestId`] reads; routes get that by being mounted
+//! through [`scoped`].
+
+use std::future::Future;
+
+use rand::Rng;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::route::{Handler, Outcome};
+use rocket::{Data, Request, Response, Route};
+use tracing::Instrument;
+
+use types::request_id::accept_or_generate;
+pub use types::request_id::HEADER;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// The id of the request being handled, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Run `fut` as part of request `id`: in its span, with [`current`] set.
+pub async fn scope<F: Future>(id: String, fut: F) -> F::Output {
+    let span = tracing::info_span!("request", id = %id);
+    CURRENT.scope(id, fut).instrument(span).await
+}
+
+/// `.with_request_id()` on calls to plugins.
+pub trait WithRequestId {
+    /// Send the current request's id along, if there is one.
+    fn with_request_id(self) -> Self;
+}
+
+impl WithRequestId for reqwest::RequestBuilder {
+    fn with_request_id(self) -> Self {
+        match current() {
+            Some(id) => self.header(HEADER, id),
+            None => self,
+        }
+    }
+}
+
+/// `req`'s id, decided once per request.
+pub fn of<'r>(req: &'r Request<'_>) -> &'r str {
+    &req.local_cache(|| {
+        let incoming = req.headers().get_one(HEADER);
+        RequestId(accept_or_generate(incoming, || rand::thread_rng().gen()))
+    })
+    .0
+}
+
+struct RequestId(String);
+
+/// `routes` with each handler run inside its request's [`scope`].
+pub fn scoped(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Scoped(route.handler));
+            route
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Scoped(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Scoped {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        scope(of(req).to_string(), self.0.handle(req, data)).await
+    }
+}
+
+/// Puts the id on every response, including errors and static files.
+pub struct Tag;
+
+#[rocket::async_trait]
+impl Fairing for Tag {
+    fn info(&self) -> Info {
+        Info {
+            name: "X-Request-Id",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        res.set_header(Header::new(HEADER, of(req).to_string()));
+    }
+}
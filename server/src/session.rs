@@ -0,0 +1,192 @@
+//! Login sessions.
+//!
+//! `/api/login` hands out an opaque random token as an HttpOnly cookie. Only
+//! the token's SHA-256 is kept, persisted to `<data_dir>/sessions.json` so
+//! logins survive a restart.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::store::{self, StoreError};
+
+pub const SESSION_COOKIE: &str = "session";
+
+pub struct SessionStore {
+    path: PathBuf,
+    ttl: Duration,
+    /// Keyed by hex SHA-256 of the cookie value.
+    sessions: Mutex<HashMap<String, Session>>,
+    /// Serializes writes of `sessions.json`.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    created: DateTime<Utc>,
+    expires: DateTime<Utc>,
+}
+
+impl SessionStore {
+    /// Load persisted sessions from `path` (missing file → none), dropping
+    /// any that already expired.
+    pub async fn open(path: impl Into<PathBuf>, ttl: Duration) -> Result<Self, StoreError> {
+        let path = path.into();
+        let mut sessions: HashMap<String, Session> = store::load_json(&path).await?;
+        let now = Utc::now();
+        sessions.retain(|_, s| s.expires > now);
+        Ok(Self {
+            path,
+            ttl,
+            sessions: Mutex::new(sessions),
+            write_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Start a new session; returns the cookie value.
+    pub async fn create(&self) -> Result<String, StoreError> {
+        let mut raw = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut raw);
+        let token = hex::encode(raw);
+        let now = Utc::now();
+        let session = Session {
+            created: now,
+            expires: now + self.ttl,
+        };
+        {
+            let mut sessions = self.sessions.lock().expect("session lock");
+            sessions.retain(|_, s| s.expires > now);
+            sessions.insert(hash_token(&token), session);
+        }
+        self.persist().await?;
+        Ok(token)
+    }
+
+    pub fn is_valid(&self, token: &str) -> bool {
+        let sessions = self.sessions.lock().expect("session lock");
+        sessions
+            .get(&hash_token(token))
+            .is_some_and(|s| s.expires > Utc::now())
+    }
+
+    pub async fn revoke(&self, token: &str) -> Result<(), StoreError> {
+        let removed = self
+            .sessions
+            .lock()
+            .expect("session lock")
+            .remove(&hash_token(token))
+            .is_some();
+        if removed {
+            self.persist().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn revoke_all(&self) -> Result<(), StoreError> {
+        self.sessions.lock().expect("session lock").clear();
+        self.persist().await
+    }
+
+    async fn persist(&self) -> Result<(), StoreError> {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = self.sessions.lock().expect("session lock").clone();
+        store::save_json(&self.path, &snapshot).await
+    }
+}
+
+/// Check `password` against the PHC-format hash from `Config::password_hash`.
+/// CPU-heavy; call from a blocking task.
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Argon2id PHC string of `password`, for `password_hash`.
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt)?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn store(dir: &tempfile::TempDir, ttl: Duration) -> SessionStore {
+        SessionStore::open(dir.path().join("sessions.json"), ttl)
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn sessions_are_valid_until_revoked_and_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = store(&dir, Duration::hours(1)).await;
+        let a = sessions.create().await.unwrap();
+        let b = sessions.create().await.unwrap();
+        assert!(sessions.is_valid(&a) && sessions.is_valid(&b));
+        assert!(!sessions.is_valid("not a session"));
+
+        sessions.revoke(&a).await.unwrap();
+        assert!(!sessions.is_valid(&a));
+        assert!(sessions.is_valid(&b));
+
+        let reopened = store(&dir, Duration::hours(1)).await;
+        assert!(!reopened.is_valid(&a));
+        assert!(reopened.is_valid(&b));
+    }
+
+    #[rocket::async_test]
+    async fn logging_out_everywhere_revokes_every_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = store(&dir, Duration::hours(1)).await;
+        let tokens = [
+            sessions.create().await.unwrap(),
+            sessions.create().await.unwrap(),
+        ];
+        sessions.revoke_all().await.unwrap();
+        assert!(tokens.iter().all(|t| !sessions.is_valid(t)));
+        let reopened = store(&dir, Duration::hours(1)).await;
+        assert!(tokens.iter().all(|t| !reopened.is_valid(t)));
+    }
+
+    #[rocket::async_test]
+    async fn expired_sessions_are_not_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = store(&dir, Duration::zero()).await;
+        let token = sessions.create().await.unwrap();
+        assert!(!sessions.is_valid(&token));
+    }
+
+    #[test]
+    fn only_the_hashed_password_verifies() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(&hash, "correct horse"));
+        assert!(!verify_password(&hash, "correct horse "));
+        assert!(!verify_password("not a phc string", "correct horse"));
+        assert!(!verify_password("", ""));
+    }
+}
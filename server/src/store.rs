@@ -0,0 +1,39 @@
+//! Helpers for the small JSON files the server keeps in `data_dir`
+//! (`sessions.json`, `tokens.json`, ...).
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Read `path` as JSON; a missing file yields `T::default()`.
+pub async fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, StoreError> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(s) => Ok(serde_json::from_str(&s)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write `value` as owner-only JSON via a temp file + rename, so a crash
+/// never leaves a torn file behind.
+pub async fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), StoreError> {
+    let data = serde_json::to_vec(value)?;
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, data).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,197 @@
+//! Runs plugin processes for `[[plugin]]` entries that have a `command`.
+//!
+//! Each plugin is restarted with exponential backoff when it exits, its
+//! stdout and stderr are appended to `<data_dir>/logs/<name>.log`, and it
+//! gets SIGTERM (then SIGKILL) when the server shuts down or its entry is
+//! removed or changed by a config reload.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::process::{Child, Command};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::config::PluginEntry;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A run this long resets the backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Grace period between SIGTERM and SIGKILL.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to launch for one plugin; a change to any of it restarts the plugin.
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+    command: Vec<String>,
+    working_dir: Option<PathBuf>,
+    env: HashMap<String, String>,
+}
+
+impl Spec {
+    fn from_entry(entry: &PluginEntry) -> Option<Self> {
+        Some(Self {
+            command: entry.command.clone()?,
+            working_dir: entry.working_dir.clone(),
+            env: entry.env.clone(),
+        })
+    }
+}
+
+struct Running {
+    spec: Spec,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Clone)]
+pub struct Supervisor {
+    log_dir: PathBuf,
+    running: Arc<Mutex<HashMap<String, Running>>>,
+}
+
+impl Supervisor {
+    pub fn new(log_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            log_dir: log_dir.into(),
+            running: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Bring the running processes in line with `entries`: start new ones,
+    /// stop removed ones, restart those whose command, directory or
+    /// environment changed.
+    pub async fn apply(&self, entries: &[PluginEntry]) {
+        let wanted: HashMap<&str, Spec> = entries
+            .iter()
+            .filter_map(|e| Some((e.name.as_str(), Spec::from_entry(e)?)))
+            .collect();
+        let mut running = self.running.lock().await;
+
+        let outdated: Vec<String> = running
+            .iter()
+            .filter(|(name, r)| wanted.get(name.as_str()) != Some(&r.spec))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in outdated {
+            if let Some(r) = running.remove(&name) {
+                stop(&name, r).await;
+            }
+        }
+
+        for (name, spec) in wanted {
+            if running.contains_key(name) {
+                continue;
+            }
+            let (stop, stopped) = watch::channel(false);
+            let log_path = self.log_dir.join(format!("{}.log", name));
+            let task = tokio::spawn(supervise(name.to_string(), spec.clone(), log_path, stopped));
+            running.insert(name.to_string(), Running { spec, stop, task });
+        }
+    }
+
+    /// Stop every plugin process.
+    pub async fn shutdown(&self) {
+        self.apply(&[]).await;
+    }
+}
+
+async fn stop(name: &str, running: Running) {
+    tracing::info!(plugin = %name, "stopping plugin process");
+    let _ = running.stop.send(true);
+    let _ = running.task.await;
+}
+
+async fn supervise(
+    name: String,
+    spec: Spec,
+    log_path: PathBuf,
+    mut stopped: watch::Receiver<bool>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match spawn(&spec, &log_path) {
+            Ok(mut child) => {
+                tracing::info!(plugin = %name, pid = child.id(), "plugin process started");
+                let started = Instant::now();
+                tokio::select! {
+                    status = child.wait() => match status {
+                        Ok(s) => tracing::warn!(plugin = %name, "plugin process exited: {}", s),
+                        Err(e) => tracing::warn!(plugin = %name, "plugin process lost: {}", e),
+                    },
+                    _ = stopped.changed() => {
+                        terminate(&name, &mut child).await;
+                        return;
+                    }
+                }
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = MIN_BACKOFF;
+                }
+            }
+            Err(e) => {
+                tracing::error!(plugin = %name, "unable to start {:?}: {}", spec.command, e)
+            }
+        }
+        tracing::info!(plugin = %name, "restarting plugin process in {:?}", backoff);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = stopped.changed() => return,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn spawn(spec: &Spec, log_path: &Path) -> std::io::Result<Child> {
+    if let Some(dir) = log_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    writeln!(
+        log,
+        "--- {} starting {:?}",
+        chrono::Utc::now(),
+        spec.command
+    )?;
+
+    let (program, args) = spec
+        .command
+        .split_first()
+        .ok_or_else(|| std::io::Error::other("empty command"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .envs(&spec.env)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .kill_on_drop(true);
+    if let Some(dir) = &spec.working_dir {
+        cmd.current_dir(dir);
+    }
+    cmd.spawn()
+}
+
+/// SIGTERM, then SIGKILL if the process outlives `STOP_TIMEOUT`.
+async fn terminate(name: &str, child: &mut Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: plain syscall on a pid we spawned and haven't reaped yet.
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+    match tokio::time::timeout(STOP_TIMEOUT, child.wait()).await {
+        Ok(_) => tracing::info!(plugin = %name, "plugin process stopped"),
+        Err(_) => {
+            tracing::warn!(plugin = %name, "plugin process ignored SIGTERM, killing");
+            let _ = child.kill().await;
+        }
+    }
+}
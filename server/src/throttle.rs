@@ -0,0 +1,324 @@
+//! Brute-force protection for everything that checks a guessable
+//! credential: the login password, API tokens and plugin tokens (so the
+//! proxy and any route behind [`Caller`](crate::auth::Caller) are covered).
+//!
+//! Each failure is logged and counted against the client address (see
+//! `[auth_limit]`): after a few, the address has to wait before its next
+//! attempt, twice as long after every further failure, and eventually it's
+//! locked out. Failed password logins are also counted across all
+//! addresses; past the global limit, password logins are refused for
+//! everyone for a minute. Token misses don't count there, so junk bearer
+//! headers can't lock the owner out.
+//! Session cookies aren't counted: they can't be guessed, and a browser
+//! holding an expired one shouldn't lock its owner out.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::config::{AuthLimit, Config};
+use crate::reload::Live;
+
+/// Addresses remembered at most; the longest-quiet go first.
+const MAX_TRACKED: usize = 10_000;
+const GLOBAL_WINDOW: Duration = Duration::from_secs(60);
+
+pub struct AuthThrottle {
+    config: Live<Config>,
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Default)]
+struct ThrottleState {
+    clients: HashMap<IpAddr, Failures>,
+    /// Recent failed password logins from anywhere, oldest first.
+    global: VecDeque<Instant>,
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    blocked_until: Option<Instant>,
+}
+
+/// Where a request came from, for throttling and the log.
+#[derive(Debug, Clone)]
+pub struct ClientAddr {
+    pub ip: Option<IpAddr>,
+    /// The raw `X-Forwarded-For` header, logged whether or not it's trusted.
+    pub forwarded_for: Option<String>,
+}
+
+impl AuthThrottle {
+    pub fn new(config: Live<Config>) -> Self {
+        Self {
+            config,
+            state: Mutex::new(ThrottleState::default()),
+        }
+    }
+
+    /// How long `client` still has to wait before it may try a credential.
+    /// `password` also applies the global limit.
+    pub fn wait(&self, client: &ClientAddr, password: bool) -> Option<Duration> {
+        self.wait_at(client, password, Instant::now())
+    }
+
+    fn wait_at(&self, client: &ClientAddr, password: bool, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().expect("throttle lock");
+        let own = client
+            .ip
+            .and_then(|ip| state.clients.get(&ip))
+            .and_then(|f| f.blocked_until)
+            .and_then(|until| until.checked_duration_since(now));
+        let global = if password {
+            let limit = self.config.load().auth_limit.global_failures_per_minute as usize;
+            prune_global(&mut state.global, now);
+            state
+                .global
+                .front()
+                .filter(|_| state.global.len() >= limit)
+                .and_then(|&t| (t + GLOBAL_WINDOW).checked_duration_since(now))
+        } else {
+            None
+        };
+        own.max(global).filter(|d| !d.is_zero())
+    }
+
+    /// Record a failed `what` (`login`, `api token`, `plugin token`).
+    /// `password` also counts it towards the global limit.
+    pub fn failure(&self, client: &ClientAddr, what: &str, password: bool) {
+        self.failure_at(client, what, password, Instant::now())
+    }
+
+    fn failure_at(&self, client: &ClientAddr, what: &str, password: bool, now: Instant) {
+        let limits = self.config.load().auth_limit.clone();
+        let mut state = self.state.lock().expect("throttle lock");
+        if password {
+            state.global.push_back(now);
+            prune_global(&mut state.global, now);
+        }
+        let Some(ip) = client.ip else {
+            tracing::warn!(forwarded_for = ?client.forwarded_for, "failed {} from unknown address", what);
+            return;
+        };
+        if !state.clients.contains_key(&ip) && state.clients.len() >= MAX_TRACKED {
+            forget_oldest(&mut state.clients, &limits, now);
+        }
+        let entry = state.clients.entry(ip).or_insert(Failures {
+            count: 0,
+            last: now,
+            blocked_until: None,
+        });
+        if now.duration_since(entry.last) >= Duration::from_secs(limits.lockout_secs) {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last = now;
+        entry.blocked_until = block_for(entry.count, &limits).map(|d| now + d);
+        match entry.count {
+            n if n >= limits.lockout_after => tracing::warn!(
+                %ip, forwarded_for = ?client.forwarded_for, failures = n,
+                "failed {}; address locked out for {}s", what, limits.lockout_secs
+            ),
+            n => tracing::warn!(
+                %ip, forwarded_for = ?client.forwarded_for, failures = n,
+                "failed {}", what
+            ),
+        }
+    }
+
+    /// `client` logged in: its failures are forgiven. Only a correct
+    /// password does this, so a valid token can't be used to reset the
+    /// count between password guesses.
+    pub fn success(&self, client: &ClientAddr) {
+        if let Some(ip) = client.ip {
+            self.state
+                .lock()
+                .expect("throttle lock")
+                .clients
+                .remove(&ip);
+        }
+    }
+}
+
+/// The wait after the `failures`th failure in a row.
+fn block_for(failures: u32, limits: &AuthLimit) -> Option<Duration> {
+    let lockout = Duration::from_secs(limits.lockout_secs);
+    if failures >= limits.lockout_after {
+        return Some(lockout);
+    }
+    let over = failures.checked_sub(limits.free_attempts)?;
+    if over == 0 {
+        return None;
+    }
+    let backoff = Duration::from_secs(1u64 << (over - 1).min(30));
+    Some(backoff.min(lockout))
+}
+
+fn prune_global(global: &mut VecDeque<Instant>, now: Instant) {
+    while global
+        .front()
+        .is_some_and(|&t| now.duration_since(t) >= GLOBAL_WINDOW)
+    {
+        global.pop_front();
+    }
+}
+
+fn forget_oldest(clients: &mut HashMap<IpAddr, Failures>, limits: &AuthLimit, now: Instant) {
+    let quiet = Duration::from_secs(limits.lockout_secs);
+    clients.retain(|_, f| now.duration_since(f.last) < quiet);
+    if clients.len() >= MAX_TRACKED {
+        if let Some(ip) = clients
+            .iter()
+            .min_by_key(|(_, f)| f.last)
+            .map(|(ip, _)| *ip)
+        {
+            clients.remove(&ip);
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientAddr {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientAddr::of(req))
+    }
+}
+
+impl ClientAddr {
+    pub fn of(req: &Request<'_>) -> Self {
+        let trust = req
+            .rocket()
+            .state::<Live<Config>>()
+            .is_some_and(|c| c.load().auth_limit.trust_forwarded_for);
+        let forwarded_for = req.headers().get_one("X-Forwarded-For");
+        Self::resolve(req.remote().map(|r| r.ip()), forwarded_for, trust)
+    }
+
+    /// The address of a request from `remote` with `forwarded_for`.
+    fn resolve(remote: Option<IpAddr>, forwarded_for: Option<&str>, trust: bool) -> Self {
+        // The last entry is the one added by the proxy in front of us; the
+        // rest came from the client and can be anything.
+        let forwarded = forwarded_for
+            .filter(|_| trust)
+            .and_then(|h| h.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        Self {
+            ip: forwarded.or(remote),
+            forwarded_for: forwarded_for.map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    /// 2 free attempts, locked out for 100 s after the 5th failure, and
+    /// password logins refused for everyone after 3 wrong passwords a minute.
+    fn throttle() -> AuthThrottle {
+        let config: Config = toml::from_str(
+            "port = 0\npassword_hash = \"\"\n[auth_limit]\nfree_attempts = 2\nlockout_after = 5\nlockout_secs = 100\nglobal_failures_per_minute = 3",
+        )
+        .unwrap();
+        AuthThrottle::new(Live::new(config))
+    }
+
+    fn client(ip: &str) -> ClientAddr {
+        ClientAddr {
+            ip: Some(ip.parse().unwrap()),
+            forwarded_for: None,
+        }
+    }
+
+    #[test]
+    fn waits_double_after_the_free_attempts_until_the_lockout() {
+        let throttle = throttle();
+        let a = client("192.0.2.1");
+        let t = Instant::now();
+        let mut waits = Vec::new();
+        for _ in 0..5 {
+            throttle.failure_at(&a, "api token", false, t);
+            waits.push(throttle.wait_at(&a, false, t));
+        }
+        assert_eq!(
+            waits,
+            [None, None, Some(SEC), Some(2 * SEC), Some(100 * SEC)]
+        );
+        assert_eq!(throttle.wait_at(&client("192.0.2.2"), false, t), None);
+    }
+
+    #[test]
+    fn lockouts_expire_and_are_forgotten() {
+        let throttle = throttle();
+        let a = client("192.0.2.1");
+        let t = Instant::now();
+        for _ in 0..5 {
+            throttle.failure_at(&a, "login", true, t);
+        }
+        assert_eq!(throttle.wait_at(&a, false, t + 99 * SEC), Some(SEC));
+        assert_eq!(throttle.wait_at(&a, false, t + 100 * SEC), None);
+        // A failure after `lockout_secs` of quiet starts counting afresh.
+        throttle.failure_at(&a, "login", false, t + 100 * SEC);
+        assert_eq!(throttle.wait_at(&a, false, t + 100 * SEC), None);
+    }
+
+    #[test]
+    fn a_correct_password_forgives_failures() {
+        let throttle = throttle();
+        let a = client("192.0.2.1");
+        let t = Instant::now();
+        for _ in 0..4 {
+            throttle.failure_at(&a, "login", false, t);
+        }
+        throttle.success(&a);
+        assert_eq!(throttle.wait_at(&a, false, t), None);
+    }
+
+    #[test]
+    fn wrong_passwords_everywhere_hold_up_password_logins_for_a_minute() {
+        let throttle = throttle();
+        let t = Instant::now();
+        for ip in ["192.0.2.1", "192.0.2.2", "192.0.2.3"] {
+            throttle.failure_at(&client(ip), "login", true, t);
+        }
+        let fresh = client("198.51.100.1");
+        assert_eq!(throttle.wait_at(&fresh, true, t), Some(60 * SEC));
+        assert_eq!(throttle.wait_at(&fresh, false, t), None);
+        assert_eq!(throttle.wait_at(&fresh, true, t + 60 * SEC), None);
+    }
+
+    #[test]
+    fn token_misses_only_count_per_address() {
+        let throttle = throttle();
+        let t = Instant::now();
+        for i in 0..20 {
+            throttle.failure_at(&client(&format!("192.0.2.{}", i)), "api token", false, t);
+        }
+        assert_eq!(throttle.wait_at(&client("198.51.100.1"), true, t), None);
+    }
+
+    #[test]
+    fn forwarded_for_is_used_only_when_trusted_and_only_its_last_entry() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let header = Some("203.0.113.9, 192.0.2.7");
+        let ip = |header, trust| ClientAddr::resolve(Some(proxy), header, trust).ip;
+        assert_eq!(ip(header, false), Some(proxy));
+        assert_eq!(ip(header, true), Some("192.0.2.7".parse().unwrap()));
+        assert_eq!(ip(Some("192.0.2.7, junk"), true), Some(proxy));
+        assert_eq!(ip(None, true), Some(proxy));
+        assert_eq!(
+            ClientAddr::resolve(Some(proxy), header, false)
+                .forwarded_for
+                .as_deref(),
+            header
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Scoped API tokens for scripts and automations.
+//!
+//! Tokens come from two places: `[[api_token]]` entries in `config.toml`
+//! (fixed, can't be revoked at runtime) and `/api/tokens/create`, which
+//! persists them to `<data_dir>/tokens.json`. Either way only the SHA-256 of
+//! the secret is stored.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::Scope;
+use crate::config::{ApiTokenEntry, Config};
+use crate::reload::Live;
+use crate::store::{self, StoreError};
+
+pub struct TokenStore {
+    path: PathBuf,
+    /// `[[api_token]]` entries are read from the live config, so reloads
+    /// pick them up.
+    config: Live<Config>,
+    created: RwLock<Vec<ApiTokenEntry>>,
+    /// Serializes writes of `tokens.json`.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+/// What `/api/tokens` lists: everything but the hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires: Option<DateTime<Utc>>,
+    /// `true` for `[[api_token]]` entries, which only `config.toml` can change.
+    pub from_config: bool,
+}
+
+impl TokenStore {
+    pub async fn open(path: impl Into<PathBuf>, config: Live<Config>) -> Result<Self, TokenError> {
+        let path = path.into();
+        let created: Vec<ApiTokenEntry> = store::load_json(&path).await?;
+        Ok(Self {
+            path,
+            config,
+            created: RwLock::new(created),
+            write_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The unexpired token whose secret is `secret`, if any.
+    pub fn lookup(&self, secret: &str) -> Option<ApiTokenEntry> {
+        let hash = hash_secret(secret);
+        let now = Utc::now();
+        let config = self.config.load();
+        let created = self.created.read().expect("token lock");
+        config
+            .api_token
+            .iter()
+            .chain(created.iter())
+            .find(|t| t.token_sha256.eq_ignore_ascii_case(&hash))
+            .filter(|t| t.expires.is_none_or(|e| e > now))
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<TokenInfo> {
+        let created = self.created.read().expect("token lock");
+        let info = |t: &ApiTokenEntry, from_config| TokenInfo {
+            name: t.name.clone(),
+            scopes: t.scopes.clone(),
+            expires: t.expires,
+            from_config,
+        };
+        self.config
+            .load()
+            .api_token
+            .iter()
+            .map(|t| info(t, true))
+            .chain(created.iter().map(|t| info(t, false)))
+            .collect()
+    }
+
+    /// Mint a token; returns the secret, which is never shown again.
+    pub async fn create(
+        &self,
+        name: String,
+        scopes: Vec<Scope>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<String, TokenError> {
+        let secret = generate_secret();
+        {
+            let config = self.config.load();
+            let mut created = self.created.write().expect("token lock");
+            if config
+                .api_token
+                .iter()
+                .chain(created.iter())
+                .any(|t| t.name == name)
+            {
+                return Err(TokenError::NameTaken(name));
+            }
+            created.push(ApiTokenEntry {
+                name,
+                token_sha256: hash_secret(&secret),
+                scopes,
+                expires,
+            });
+        }
+        self.persist().await?;
+        Ok(secret)
+    }
+
+    pub async fn revoke(&self, name: &str) -> Result<(), TokenError> {
+        if self.config.load().api_token.iter().any(|t| t.name == name) {
+            return Err(TokenError::FromConfig(name.to_string()));
+        }
+        let removed = {
+            let mut created = self.created.write().expect("token lock");
+            let before = created.len();
+            created.retain(|t| t.name != name);
+            created.len() != before
+        };
+        if !removed {
+            return Err(TokenError::NotFound(name.to_string()));
+        }
+        self.persist().await
+    }
+
+    async fn persist(&self) -> Result<(), TokenError> {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = self.created.read().expect("token lock").clone();
+        Ok(store::save_json(&self.path, &snapshot).await?)
+    }
+}
+
+/// A new random secret, 32 bytes in hex.
+pub fn generate_secret() -> String {
+    let mut raw = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut raw);
+    hex::encode(raw)
+}
+
+/// Hex SHA-256, the form `token_sha256` is written in.
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("{0}")]
+    Store(#[from] StoreError),
+    #[error("a token named {0} already exists")]
+    NameTaken(String),
+    #[error("token {0} is declared in config.toml")]
+    FromConfig(String),
+    #[error("no token named {0}")]
+    NotFound(String),
+}
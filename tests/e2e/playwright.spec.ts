@@ -13,7 +13,7 @@
 // (Tests are tagged with prefixes; you can filter via --grep "tier1" or
 //  --grep "baseline" if you only want one set.)
 
-import { test, expect, type Page } from "@playwright/test";
+import { test, expect, type APIRequestContext, type Page } from "@playwright/test";
 
 const PORT = 18002;
 const PASSWORD = "smoke-test-pwd";
@@ -28,11 +28,26 @@ const TIER1_PLUGINS = [
   "timeline_plugin_media_scan",
 ];
 
-async function setPasswordCookie(page: Page) {
+// POST /api/login and return the `session=<token>` pair from Set-Cookie.
+// The server marks the cookie Secure, so we re-add it by hand rather than
+// relying on the browser to keep it over plain http.
+async function login(request: APIRequestContext): Promise<string> {
+  const res = await request.post(`${BASE}/api/login`, {
+    data: { password: PASSWORD },
+  });
+  expect(res.ok()).toBe(true);
+  const setCookie = res.headers()["set-cookie"] ?? "";
+  const pair = setCookie.split(";")[0];
+  expect(pair.startsWith("session=")).toBe(true);
+  return pair;
+}
+
+async function setSessionCookie(page: Page) {
+  const pair = await login(page.request);
   await page.context().addCookies([
     {
-      name: "pwd",
-      value: PASSWORD,
+      name: "session",
+      value: pair.slice("session=".length),
       domain: "127.0.0.1",
       path: "/",
       sameSite: "Lax",
@@ -54,22 +69,28 @@ test("baseline: unauth user sees login form", async ({ page }) => {
 });
 
 test("baseline: with valid cookie, timeline body renders", async ({ page }) => {
-  await setPasswordCookie(page);
+  await setSessionCookie(page);
   await page.goto(`${BASE}/timeline`);
   await expect(page.locator(".timelineBar")).toBeVisible({ timeout: 15_000 });
 });
 
 test("baseline: app selector rendered (may be empty)", async ({ page }) => {
-  await setPasswordCookie(page);
+  await setSessionCookie(page);
   await page.goto(`${BASE}/timeline`);
   await expect(page.locator(".appSelector")).toBeAttached({ timeout: 15_000 });
 });
 
+test("baseline: errors page lists the error log", async ({ page }) => {
+  await setSessionCookie(page);
+  await page.goto(`${BASE}/errors`);
+  await expect(page.locator(".infoWrapper, .errorList")).toBeVisible({ timeout: 15_000 });
+});
+
 // ---------------- tier-1 (requires serve_tier1.sh stack) ----------------
 
 test("tier1: /api/plugins returns all six manifests", async ({ request }) => {
   const res = await request.post(`${BASE}/api/plugins`, {
-    headers: { Cookie: `pwd=${PASSWORD}` },
+    headers: { Cookie: await login(request) },
   });
   expect(res.ok()).toBe(true);
   const body = await res.json();
@@ -86,7 +107,7 @@ test("tier1: /api/events fan-out includes every plugin", async ({ request }) =>
   const tomorrow = new Date(day.getTime() + 24 * 60 * 60 * 1000);
   const end = tomorrow.toISOString();
   const res = await request.post(`${BASE}/api/events`, {
-    headers: { Cookie: `pwd=${PASSWORD}`, "Content-Type": "application/json" },
+    headers: { Cookie: await login(request), "Content-Type": "application/json" },
     data: { start, end },
   });
   expect(res.ok()).toBe(true);
@@ -95,16 +116,50 @@ test("tier1: /api/events fan-out includes every plugin", async ({ request }) =>
   for (const plugin of TIER1_PLUGINS) {
     // Some plugins may have empty arrays if their request_loop hasn't
     // run yet; we just need the key present in the fan-out map.
-    expect(body.Ok[plugin]).toBeDefined();
+    expect(body.Ok.events[plugin]).toBeDefined();
+    expect(body.Ok.plugins[plugin].outcome.state).toBe("ok");
   }
 });
 
+test("tier1: paged /api/events caps each plugin at the limit", async ({ request }) => {
+  const day = new Date();
+  day.setUTCHours(0, 0, 0, 0);
+  const start = day.toISOString();
+  const end = new Date(day.getTime() + 24 * 60 * 60 * 1000).toISOString();
+  const res = await request.post(`${BASE}/api/events`, {
+    headers: { Cookie: await login(request), "Content-Type": "application/json" },
+    data: { start, end, limit: 1, order: "desc" },
+  });
+  expect(res.ok()).toBe(true);
+  const body = await res.json();
+  for (const plugin of TIER1_PLUGINS) {
+    expect(body.Ok.plugins[plugin].outcome.state).toBe("ok");
+    expect(body.Ok.events[plugin].length).toBeLessThanOrEqual(1);
+  }
+});
+
+test("tier1: /api/status reports every plugin up", async ({ request }) => {
+  const cookie = await login(request);
+  // Health is polled in the background; plugins started after the server
+  // show as down until the next round (health_interval_secs, default 30).
+  await expect
+    .poll(
+      async () => {
+        const res = await request.post(`${BASE}/api/status`, { headers: { Cookie: cookie } });
+        const body = await res.json();
+        return TIER1_PLUGINS.filter((p) => body.Ok?.[p]?.state !== "up");
+      },
+      { timeout: 45_000 },
+    )
+    .toEqual([]);
+});
+
 test("tier1: app selector renders at least one plugin icon", async ({ page }) => {
   // The AppSelect filters to plugins that have events in the
   // current_range (default first hour of day). The probe deliberately
   // injects text/usage events in that hour; we assert the weaker
   // invariant: at least one plugin icon shows up.
-  await setPasswordCookie(page);
+  await setSessionCookie(page);
   await page.goto(`${BASE}/timeline`);
   await expect(page.locator(".appSelector .iconWrap").first()).toBeAttached({
     timeout: 20_000,
@@ -112,7 +167,7 @@ test("tier1: app selector renders at least one plugin icon", async ({ page }) =>
 });
 
 test("tier1: clicking a plugin shows its events", async ({ page }) => {
-  await setPasswordCookie(page);
+  await setSessionCookie(page);
   await page.goto(`${BASE}/timeline`);
   await expect(page.locator(".appSelector .iconWrap").first()).toBeAttached({
     timeout: 20_000,
@@ -128,7 +183,7 @@ test("tier1: text-plugin event surfaces via /event/latest", async ({ page }) =>
   // for the "smoke test" body that timeline_plugin_text renders.
   page.on("console", (m) => console.log(`[browser ${m.type()}]`, m.text()));
   page.on("pageerror", (e) => console.log("[browser pageerror]", e.message));
-  await setPasswordCookie(page);
+  await setSessionCookie(page);
   await page.goto(`${BASE}/event/latest`);
 
   await expect(page.locator(".appSelector .iconWrap").first()).toBeAttached({
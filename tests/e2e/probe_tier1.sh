@@ -12,7 +12,12 @@ test -f "$RUN_DIR/env" || { echo "no runtime env file at $RUN_DIR/env" >&2; exit
 source "$RUN_DIR/env"
 
 BASE="http://127.0.0.1:$MAIN_PORT"
-COOKIE="pwd=$PASSWORD"
+COOKIE="$RUN_DIR/cookies.txt"
+
+curl -sf -c "$COOKIE" -X POST "$BASE/api/login" \
+  -H "Content-Type: application/json" \
+  -d "{\"password\":\"$PASSWORD\"}" >/dev/null \
+  || { echo "login failed" >&2; exit 1; }
 
 ok() { echo "ok [$1]"; }
 fail() { echo "FAIL [$1]: $2" >&2; exit 1; }
@@ -87,15 +92,17 @@ fi
 
 assert_present() {
   local plugin=$1
-  if ! grep -q "\"$plugin\"" <<<"$events"; then
-    fail "events" "plugin $plugin missing from /api/events response"
+  if ! jq -e --arg p "$plugin" '.Ok.events | has($p)' <<<"$events" >/dev/null; then
+    local status
+    status=$(jq -c --arg p "$plugin" '.Ok.plugins[$p]' <<<"$events")
+    fail "events" "plugin $plugin missing from /api/events response (status: $status)"
   fi
   ok "events: $plugin present in fan-out"
 }
 
 assert_present timeline_plugin_text
 # web is best-effort given the OG fetch can fail offline
-if grep -q '"timeline_plugin_web"' <<<"$events"; then
+if jq -e '.Ok.events | has("timeline_plugin_web")' <<<"$events" >/dev/null; then
   ok "events: timeline_plugin_web present in fan-out"
 else
   echo "  warn: web missing from fan-out (probably offline)"
@@ -14,7 +14,12 @@ test -f "$RUN_DIR/env" || { echo "no runtime env at $RUN_DIR/env" >&2; exit 2; }
 source "$RUN_DIR/env"
 
 BASE="http://127.0.0.1:$MAIN_PORT"
-COOKIE="pwd=$PASSWORD"
+COOKIE="$RUN_DIR/cookies.txt"
+
+curl -sf -c "$COOKIE" -X POST "$BASE/api/login" \
+  -H "Content-Type: application/json" \
+  -d "{\"password\":\"$PASSWORD\"}" >/dev/null \
+  || { echo "login failed" >&2; exit 1; }
 
 day_start=$(date -u +%Y-%m-%dT00:00:00Z)
 day_end=$(date -u -d "tomorrow" +%Y-%m-%dT00:00:00Z 2>/dev/null \
@@ -36,17 +41,17 @@ while [[ $(date +%s) -lt $deadline ]]; do
     -d "{\"start\":\"$day_start\",\"end\":\"$day_end\"}" \
     || echo "")
 
-  steam_count=$(jq -r '.Ok.timeline_plugin_steam | length // 0' <<<"$body" 2>/dev/null || echo 0)
-  spotify_count=$(jq -r '.Ok.timeline_plugin_spotify | length // 0' <<<"$body" 2>/dev/null || echo 0)
+  steam_count=$(jq -r '.Ok.events.timeline_plugin_steam | length // 0' <<<"$body" 2>/dev/null || echo 0)
+  spotify_count=$(jq -r '.Ok.events.timeline_plugin_spotify | length // 0' <<<"$body" 2>/dev/null || echo 0)
 
   if [[ "$steam_count" -gt 0 && "$steam_seen" -eq 0 ]]; then
     echo "ok [steam]  $steam_count event(s) at attempt $attempt"
-    jq -r '.Ok.timeline_plugin_steam[].title' <<<"$body" | sed 's/^/      - /'
+    jq -r '.Ok.events.timeline_plugin_steam[].title' <<<"$body" | sed 's/^/      - /'
     steam_seen=1
   fi
   if [[ "$spotify_count" -gt 0 && "$spotify_seen" -eq 0 ]]; then
     echo "ok [spotify] $spotify_count event(s) at attempt $attempt"
-    jq -r '.Ok.timeline_plugin_spotify[].title' <<<"$body" | sed 's/^/      - /'
+    jq -r '.Ok.events.timeline_plugin_spotify[].title' <<<"$body" | sed 's/^/      - /'
     spotify_seen=1
   fi
 
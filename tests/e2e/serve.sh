@@ -10,6 +10,8 @@ SANDBOX="$ROOT/tests/e2e/_run"
 LOG="$SANDBOX/server.log"
 PORT=18002
 PASSWORD="smoke-test-pwd"
+# argon2id of $PASSWORD
+PASSWORD_HASH='$argon2id$v=19$m=19456,t=2,p=1$+63T4sL8J/Xe42rxtj4i6A$WTq3Prr/3OGPNUvWAl8ieRfIZkd6gNgrTEn0IJSUHdY'
 
 SERVER_BIN="$ROOT/server/target/release/server"
 test -x "$SERVER_BIN" || {
@@ -24,12 +26,12 @@ test -d "$ROOT/frontend/dist" || (cd "$ROOT/frontend" && trunk build --release)
 mkdir -p "$SANDBOX/server" "$SANDBOX/frontend"
 cat > "$SANDBOX/server/config.toml" <<EOF
 port = $PORT
-password = "$PASSWORD"
+password_hash = '$PASSWORD_HASH'
 data_dir = "./data"
 EOF
 ln -sfn "$ROOT/frontend/dist" "$SANDBOX/frontend/dist"
 
 echo "==> serving timeline at http://127.0.0.1:$PORT"
-echo "    cookie:   pwd=$PASSWORD"
+echo "    password: $PASSWORD"
 echo "    log file: $LOG"
 exec env -C "$SANDBOX/server" RUST_LOG=info "$SERVER_BIN"
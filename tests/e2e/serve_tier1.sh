@@ -26,6 +26,8 @@ RUN_DIR="$SANDBOX/runtime"
 
 MAIN_PORT=18002
 PASSWORD="smoke-test-pwd"
+# argon2id of $PASSWORD
+PASSWORD_HASH='$argon2id$v=19$m=19456,t=2,p=1$+63T4sL8J/Xe42rxtj4i6A$WTq3Prr/3OGPNUvWAl8ieRfIZkd6gNgrTEn0IJSUHdY'
 
 # Tier-1 plugin map: name -> port
 declare -A PORTS=(
@@ -186,7 +188,7 @@ done
 
 {
   echo "port = $MAIN_PORT"
-  echo "password = \"$PASSWORD\""
+  echo "password_hash = '$PASSWORD_HASH'"
   echo "data_dir = \"$DATA_DIR\""
   echo
   for plugin in "${!PORTS[@]}"; do
@@ -265,7 +267,7 @@ wait_for_ready "main /api/auth" "http://127.0.0.1:$MAIN_PORT/index.html"
   done
 } > "$RUN_DIR/env"
 
-echo "==> stack up. main http://127.0.0.1:$MAIN_PORT  cookie pwd=$PASSWORD"
+echo "==> stack up. main http://127.0.0.1:$MAIN_PORT  password $PASSWORD"
 echo "    runtime env: $RUN_DIR/env"
 
 if [[ $mode == probe ]]; then
@@ -25,6 +25,8 @@ RUN_DIR="$SANDBOX/runtime"
 
 MAIN_PORT=18003
 PASSWORD="tier3-pwd"
+# argon2id of $PASSWORD
+PASSWORD_HASH='$argon2id$v=19$m=19456,t=2,p=1$tTAxzuBtpPZQ6UUO2WpoOQ$TI6van9VrcQTPjecTdNb+Ku81mv8qiNHUg9Qb3Fe23c'
 
 declare -A PORTS=(
   [timeline_plugin_steam]=19011
@@ -138,7 +140,7 @@ done
 
 {
   echo "port = $MAIN_PORT"
-  echo "password = \"$PASSWORD\""
+  echo "password_hash = '$PASSWORD_HASH'"
   echo "data_dir = \"$DATA_DIR\""
   echo
   for plugin in "${!PORTS[@]}"; do
@@ -210,7 +212,7 @@ wait_for_ready "main" "http://127.0.0.1:$MAIN_PORT/index.html"
 
 echo
 echo "==> tier-3 stack up"
-echo "    main:    http://127.0.0.1:$MAIN_PORT  cookie pwd=$PASSWORD"
+echo "    main:    http://127.0.0.1:$MAIN_PORT  password $PASSWORD"
 echo "    logs:    $LOG_DIR/{main,timeline_plugin_steam,timeline_plugin_spotify}.log"
 echo "    runtime: $RUN_DIR/env"
 echo
@@ -15,6 +15,9 @@ SANDBOX="$ROOT/tests/e2e/_run"
 LOG="$SANDBOX/server.log"
 PORT=18002
 PASSWORD="smoke-test-pwd"
+# argon2id of $PASSWORD
+PASSWORD_HASH='$argon2id$v=19$m=19456,t=2,p=1$+63T4sL8J/Xe42rxtj4i6A$WTq3Prr/3OGPNUvWAl8ieRfIZkd6gNgrTEn0IJSUHdY'
+JAR="$SANDBOX/cookies.txt"
 
 skip_build=0
 for arg in "$@"; do
@@ -47,23 +50,48 @@ SERVER_BIN="$ROOT/server/target/release/server"
 test -x "$SERVER_BIN" || { echo "server binary not found at $SERVER_BIN" >&2; exit 3; }
 
 # --- sandbox layout ---
-# The server reads config.toml from CWD and serves frontend/dist via the
-# relative path ../frontend/dist/. We mirror that layout under the sandbox.
 
 mkdir -p "$SANDBOX/server"
 cat > "$SANDBOX/server/config.toml" <<EOF
 port = $PORT
-password = "$PASSWORD"
-data_dir = "./data"
+password_hash = '$PASSWORD_HASH'
+data_dir = "$SANDBOX/server/data"
 EOF
-mkdir -p "$SANDBOX/frontend"
-ln -sfn "$ROOT/frontend/dist" "$SANDBOX/frontend/dist"
+
+# --- cli ---
+
+assert_cli() {
+  local desc=$1 pattern=$2
+  shift 2
+  local out
+  if ! out=$("$@" 2>&1) || ! grep -qE "$pattern" <<<"$out"; then
+    echo "FAIL [$desc]: $out" >&2
+    exit 1
+  fi
+  echo "ok [$desc]"
+}
+
+assert_cli "cli: check-config" ': ok \(0 plugins' \
+  "$SERVER_BIN" check-config --config "$SANDBOX/server/config.toml"
+echo 'port = "x"' > "$SANDBOX/bad.toml"
+if out=$("$SERVER_BIN" check-config --config "$SANDBOX/bad.toml" 2>&1) \
+  || ! grep -q "line 1" <<<"$out"; then
+  echo "FAIL [cli: check-config rejects]: $out" >&2
+  exit 1
+fi
+echo "ok [cli: check-config rejects]"
+assert_cli "cli: hash-password" '^\$argon2id\$' \
+  sh -c "echo '$PASSWORD' | '$SERVER_BIN' hash-password"
+assert_cli "cli: gen-token" '^[0-9a-f]{64}$' "$SERVER_BIN" gen-token
+# zero plugins: nothing to fail
+assert_cli "cli: check" '^$' "$SERVER_BIN" check --config "$SANDBOX/server/config.toml"
 
 # --- run ---
 
 echo "==> starting server on :$PORT (logs: $LOG)"
-( cd "$SANDBOX/server" && ROCKET_PROFILE=release RUST_LOG=info \
-  "$SERVER_BIN" > "$LOG" 2>&1 ) &
+( cd "$SANDBOX" && ROCKET_PROFILE=release RUST_LOG=info \
+  TIMELINE_CONFIG="$SANDBOX/server/config.toml" \
+  exec "$SERVER_BIN" serve --frontend-dir "$ROOT/frontend/dist" > "$LOG" 2>&1 ) &
 SERVER_PID=$!
 
 # wait for boot, with a hard timeout
@@ -108,22 +136,137 @@ assert_body "auth: no cookie" \
   '{"Err":"AuthenticationError"}' \
   "curl -s -X POST $base/api/auth"
 
-# auth with valid cookie → Ok
-assert_body "auth: valid cookie" \
+# login with the wrong password → 401, no session
+assert_status "login: wrong password" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/login \
+     -H 'Content-Type: application/json' -d '{\"password\":\"nope\"}'"
+
+# login with the right password → session cookie in $JAR
+rm -f "$JAR"
+assert_body "login: valid password" \
+  '{"Ok":null}' \
+  "curl -s -c '$JAR' -X POST $base/api/login \
+     -H 'Content-Type: application/json' -d '{\"password\":\"$PASSWORD\"}'"
+
+# auth with the session cookie → Ok
+assert_body "auth: valid session" \
   '{"Ok":null}' \
+  "curl -s -X POST $base/api/auth -b '$JAR'"
+
+# the old plaintext cookie no longer works
+assert_body "auth: legacy pwd cookie" \
+  '{"Err":"AuthenticationError"}' \
   "curl -s -X POST $base/api/auth -b 'pwd=$PASSWORD'"
 
-# events fan-out, zero plugins → empty map
+# events fan-out, zero plugins → empty map, no statuses
 assert_body "events: no plugins" \
-  '{"Ok":{}}' \
+  '{"Ok":{"events":{},"plugins":{}}}' \
   "curl -s -X POST $base/api/events -H 'Content-Type: application/json' \
      -d '{\"start\":\"2026-04-25T00:00:00Z\",\"end\":\"2026-04-25T23:59:59Z\"}' \
-     -b 'pwd=$PASSWORD'"
+     -b '$JAR'"
+
+# paged events, zero plugins → same, with no next cursor; junk cursors → 400
+assert_body "events: paged, no plugins" \
+  '{"Ok":{"events":{},"plugins":{}}}' \
+  "curl -s -X POST $base/api/events -H 'Content-Type: application/json' \
+     -d '{\"start\":\"2026-04-25T00:00:00Z\",\"end\":\"2026-04-25T23:59:59Z\",\"limit\":50,\"order\":\"desc\"}' \
+     -b '$JAR'"
+
+assert_status "events: bad cursor" "400" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/events -H 'Content-Type: application/json' \
+     -d '{\"start\":\"2026-04-25T00:00:00Z\",\"end\":\"2026-04-25T23:59:59Z\",\"cursor\":\"nope\"}' \
+     -b '$JAR'"
 
 # plugins manifest, zero plugins → []
 assert_body "plugins: no plugins" \
   '{"Ok":[]}' \
-  "curl -s -X POST $base/api/plugins -b 'pwd=$PASSWORD'"
+  "curl -s -X POST $base/api/plugins -b '$JAR'"
+
+# markers, zero plugins → no buckets; oversized requests are refused
+assert_body "markers: no plugins" \
+  '{"Ok":[]}' \
+  "curl -s -X POST $base/api/markers -H 'Content-Type: application/json' \
+     -d '{\"start\":\"2026-04-25T00:00:00Z\",\"end\":\"2026-04-26T00:00:00Z\",\"resolution\":\"15min\"}' \
+     -b '$JAR'"
+
+assert_status "markers: too many buckets" "400" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/markers -H 'Content-Type: application/json' \
+     -d '{\"start\":\"2026-01-01T00:00:00Z\",\"end\":\"2026-04-26T00:00:00Z\",\"resolution\":\"minute\"}' \
+     -b '$JAR'"
+
+# search fan-out, zero plugins → no results, no statuses
+assert_body "search: no plugins" \
+  '{"Ok":{"results":{},"plugins":{}}}' \
+  "curl -s -X POST $base/api/search -H 'Content-Type: application/json' \
+     -d '{\"query\":\"anything\"}' -b '$JAR'"
+
+assert_status "search: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/search \
+     -H 'Content-Type: application/json' -d '{\"query\":\"anything\"}'"
+
+# export, zero plugins → just the CSV header; needs a login and a known format
+assert_body "export: csv, no plugins" \
+  'plugin,title,start,end,data' \
+  "curl -s '$base/api/export?start=2026-04-25&end=2026-04-26&format=csv' -b '$JAR' | tr -d '\r'"
+
+assert_status "export: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' '$base/api/export?start=2026-04-25&end=2026-04-26&format=csv'"
+
+assert_status "export: unknown format" "400" \
+  "curl -s -o /dev/null -w '%{http_code}' '$base/api/export?start=2026-04-25&end=2026-04-26&format=xml' -b '$JAR'"
+
+# error log, nothing failed yet → []; session only, plugin token required to report
+assert_body "errors: empty" \
+  '{"Ok":[]}' \
+  "curl -s -X POST $base/api/errors -b '$JAR'"
+
+assert_status "errors: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/errors"
+
+assert_status "errors: report without plugin token" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/errors/report \
+     -H 'Content-Type: application/json' -d '{\"message\":\"boom\"}'"
+
+# X-Request-Id: kept when sent, made up otherwise, always echoed
+assert_body "request id: echoed" \
+  'x-request-id: smoke-req-1' \
+  "curl -s -o /dev/null -D - -X POST $base/api/auth -H 'X-Request-Id: smoke-req-1' \
+     | tr -d '\r' | grep -i '^x-request-id:' | tr 'A-Z' 'a-z'"
+
+assert_status "request id: generated" "1" \
+  "curl -s -o /dev/null -D - -X POST $base/api/auth | grep -ci '^x-request-id: [0-9a-f]\{32\}'"
+
+# Prometheus metrics: any valid credential
+assert_status "metrics: with cookie" "200" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/metrics -b '$JAR'"
+
+assert_status "metrics: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/metrics"
+
+# plugin health, zero plugins → {}; needs a login like /api/plugins
+assert_body "status: no plugins" \
+  '{"Ok":{}}' \
+  "curl -s -X POST $base/api/status -b '$JAR'"
+
+assert_status "status: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/status"
+
+# live push: the stream needs a login, announcing needs a plugin token
+assert_status "live: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/api/live"
+
+assert_status "live: announce without plugin token" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/live/announce -b '$JAR' \
+     -H 'Content-Type: application/json' -d '{\"events\":[]}'"
+
+# plugin proxy requires a session; unknown plugins look the same as private
+# routes to anonymous callers. With a session an unknown plugin is a 404,
+# which the SPA catcher turns into index.html (202).
+assert_status "proxy: no cookie" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/api/plugin/nope/events"
+
+assert_status "proxy: unknown plugin" "202" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/api/plugin/nope/events -b '$JAR'"
 
 # SPA fallback for unknown path → 202 with index.html
 assert_status "spa fallback: /timeline/" "202" \
@@ -136,4 +279,65 @@ assert_status "spa fallback: deep link" "202" \
 assert_status "static: /index.html" "200" \
   "curl -s -o /dev/null -w '%{http_code}' $base/index.html"
 
+# API tokens: minted from a session, scoped, sent as a bearer
+TOKEN=$(curl -s -X POST $base/api/tokens/create -b "$JAR" \
+  -H 'Content-Type: application/json' \
+  -d '{"name":"smoke","scopes":["read_events"]}' \
+  | sed -n 's/.*"token":"\([0-9a-f]*\)".*/\1/p')
+if [[ -z "$TOKEN" ]]; then
+  echo "FAIL [tokens: create]: no token in response" >&2
+  exit 1
+fi
+echo "ok [tokens: create]"
+
+assert_body "tokens: read_events reads events" \
+  '{"Ok":{"events":{},"plugins":{}}}' \
+  "curl -s -X POST $base/api/events -H 'Content-Type: application/json' \
+     -d '{\"start\":\"2026-04-25T00:00:00Z\",\"end\":\"2026-04-25T23:59:59Z\"}' \
+     -H 'Authorization: Bearer $TOKEN'"
+
+assert_status "tokens: not admin" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/tokens \
+     -H 'Authorization: Bearer $TOKEN'"
+
+assert_status "tokens: no proxy scope" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' $base/api/plugin/nope/events \
+     -H 'Authorization: Bearer $TOKEN'"
+
+curl -s -o /dev/null -X POST $base/api/tokens/revoke -b "$JAR" \
+  -H 'Content-Type: application/json' -d '{"name":"smoke"}'
+assert_body "tokens: revoked" \
+  '{"Err":"AuthenticationError"}' \
+  "curl -s -X POST $base/api/auth -H 'Authorization: Bearer $TOKEN'"
+
+# config reload is session-only and re-applies the unchanged config
+assert_status "config: reload without session" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/config/reload"
+
+assert_status "config: reload" "200" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/config/reload -b '$JAR'"
+
+# the deployment check is session-only; with no plugins it has nothing to say
+assert_status "check: without session" "401" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/check"
+
+assert_body "check: no plugins" '{"Ok":[]}' \
+  "curl -s -X POST $base/api/check -b '$JAR'"
+
+# logout ends the session server-side, even if the cookie is replayed
+curl -s -o /dev/null -X POST $base/api/logout -b "$JAR"
+assert_body "auth: after logout" \
+  '{"Err":"AuthenticationError"}' \
+  "curl -s -X POST $base/api/auth -b '$JAR'"
+
+# brute-force protection: after a few wrong passwords this address has to
+# wait, even for the right one (last, since it locks the runner out)
+for _ in 1 2 3 4 5 6; do
+  curl -s -o /dev/null -X POST $base/api/login \
+    -H 'Content-Type: application/json' -d '{"password":"wrong"}'
+done
+assert_status "login: throttled" "429" \
+  "curl -s -o /dev/null -w '%{http_code}' -X POST $base/api/login \
+     -H 'Content-Type: application/json' -d '{\"password\":\"$PASSWORD\"}'"
+
 echo "==> all smoke probes passed"
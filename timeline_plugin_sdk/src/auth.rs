@@ -1,10 +1,19 @@
 //! Bearer-token request guard. The main timeline server sends
 //! `Authorization: Bearer <token>` on every proxied call; the SDK rejects
 //! anything without a matching token (the `Plugin::token` from `config.toml`).
+//!
+//! Routes marked [`RouteRule::signed`](crate::RouteRule::signed) are also
+//! called without it, by whoever holds a link from [`UrlSigner`]; they take
+//! [`SignedUrl`] instead.
 
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::State;
+use sha2::Sha256;
 
 use crate::launch::PluginState;
 
@@ -38,6 +47,92 @@ impl<'r> FromRequest<'r> for AuthedClient {
     }
 }
 
+/// Passes requests with the bearer token, like [`AuthedClient`], and
+/// anonymous ones whose URL was signed by the plugin's [`UrlSigner`] and
+/// hasn't expired.
+pub struct SignedUrl;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignedUrl {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.guard::<AuthedClient>().await {
+            Outcome::Success(_) => return Outcome::Success(SignedUrl),
+            Outcome::Error((_, AuthError::Missing)) => {}
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        }
+        let Some(state) = req.rocket().state::<PluginState>() else {
+            return Outcome::Error((Status::InternalServerError, AuthError::StateMissing));
+        };
+        let (Some(Ok(expires)), Some(Ok(sig))) = (
+            req.query_value::<i64>("expires"),
+            req.query_value::<&str>("sig"),
+        ) else {
+            return Outcome::Error((Status::Unauthorized, AuthError::Missing));
+        };
+        if expires < Utc::now().timestamp() {
+            return Outcome::Error((Status::Unauthorized, AuthError::Expired));
+        }
+        if state.signer.verify(req.uri().path().as_str(), expires, sig) {
+            Outcome::Success(SignedUrl)
+        } else {
+            Outcome::Error((Status::Unauthorized, AuthError::BadSignature))
+        }
+    }
+}
+
+/// Signs links to the plugin's routes with its token, for [`SignedUrl`].
+/// The signature covers the path and the expiry, not the rest of the query.
+#[derive(Clone)]
+pub struct UrlSigner {
+    plugin_name: String,
+    key: String,
+}
+
+impl UrlSigner {
+    pub fn new(plugin_name: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            plugin_name: plugin_name.into(),
+            key: key.into(),
+        }
+    }
+
+    /// A link to `path` (relative to the plugin root and already
+    /// percent-encoded, e.g. `file/report.pdf`) through the main server's
+    /// proxy, valid for `ttl`: `/api/plugin/<name>/file/report.pdf?expires=..&sig=..`.
+    pub fn sign(&self, path: &str, ttl: Duration) -> String {
+        let path = format!("/{}", path.trim_start_matches('/'));
+        let expires = Utc::now()
+            .timestamp()
+            .saturating_add_unsigned(ttl.as_secs());
+        let sig = hex::encode(self.mac(&path, expires).finalize().into_bytes());
+        format!(
+            "/api/plugin/{}{}?expires={}&sig={}",
+            self.plugin_name, path, expires, sig
+        )
+    }
+
+    /// Whether `sig` is this signer's signature of `path` (as the plugin
+    /// receives it) until `expires`.
+    pub fn verify(&self, path: &str, expires: i64, sig: &str) -> bool {
+        let Ok(sig) = hex::decode(sig) else {
+            return false;
+        };
+        self.mac(path, expires).verify_slice(&sig).is_ok()
+    }
+
+    fn mac(&self, path: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(self.key.as_bytes()).expect("HMAC takes any key length");
+        mac.update(path.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
 fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
@@ -55,4 +150,92 @@ pub enum AuthError {
     WrongScheme,
     BadToken,
     StateMissing,
+    /// A signed URL past its `expires`.
+    Expired,
+    BadSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+    use rocket::{get, routes, Route};
+
+    use super::*;
+    use crate::launch::tests::{client, TOKEN};
+    use crate::manifest::{Manifest, RouteRule, Style};
+    use crate::plugin::{Context, Plugin};
+    use crate::{APIResult, CompressedEvent, TimeRange};
+
+    #[get("/file/<name>")]
+    fn file(_auth: SignedUrl, name: &str) -> String {
+        format!("contents of {}", name)
+    }
+
+    /// Serves `/file/<name>` to signed links.
+    struct Files;
+
+    impl Plugin for Files {
+        async fn new(_ctx: Context) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                name: "files".to_string(),
+                display_name: "Files".to_string(),
+                style: Style::Acc1,
+                icon: None,
+                web_entry: None,
+            }
+        }
+
+        async fn events(&self, _range: TimeRange) -> APIResult<Vec<CompressedEvent>> {
+            Ok(Vec::new())
+        }
+
+        fn routes(&self) -> Vec<Route> {
+            routes![file]
+        }
+
+        fn route_access(&self) -> Vec<RouteRule> {
+            vec![RouteRule::signed("file")]
+        }
+    }
+
+    #[rocket::async_test]
+    async fn signed_links_open_signed_routes_until_they_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(Files, dir.path()).await;
+        let signer = UrlSigner::new("files", TOKEN);
+        // The main server forwards what follows the plugin name, without the
+        // bearer token.
+        let status = |link: &str| {
+            let path = link.strip_prefix("/api/plugin/files").unwrap().to_string();
+            let client = &client;
+            async move { client.get(path).dispatch().await.status() }
+        };
+
+        let link = signer.sign("file/a.pdf", Duration::from_secs(60));
+        assert_eq!(status(&link).await, Status::Ok);
+        assert_eq!(
+            status(&link.replace("a.pdf", "b.pdf")).await,
+            Status::Unauthorized
+        );
+        assert_eq!(
+            status("/api/plugin/files/file/a.pdf").await,
+            Status::Unauthorized
+        );
+
+        let expires = Utc::now().timestamp() - 1;
+        let sig = hex::encode(signer.mac("/file/a.pdf", expires).finalize().into_bytes());
+        let expired = format!(
+            "/api/plugin/files/file/a.pdf?expires={}&sig={}",
+            expires, sig
+        );
+        assert_eq!(status(&expired).await, Status::Unauthorized);
+
+        let other = UrlSigner::new("files", "another plugin's token");
+        let forged = other.sign("file/a.pdf", Duration::from_secs(60));
+        assert_eq!(status(&forged).await, Status::Unauthorized);
+    }
 }
@@ -31,6 +31,10 @@ pub struct PluginConfig {
     pub data_dir: PathBuf,
     #[serde(default)]
     pub error_report_url: Option<Url>,
+    /// Where the main timeline server listens (e.g. `http://127.0.0.1:8002`).
+    /// Needed for [`Notifier`](crate::Notifier) calls; unset → they're skipped.
+    #[serde(default)]
+    pub server_url: Option<Url>,
 }
 
 fn default_display_name() -> Option<String> {
@@ -3,15 +3,19 @@
 //! One database per plugin. Events are indexed by `(start_ts, end_ts)`. The
 //! payload is JSON-encoded so plugins can keep storing arbitrary
 //! `serde_json::Value` shapes exactly like they did with MongoDB.
+//!
+//! `events_fts` is an FTS5 index over `title` and `data`, kept in sync by
+//! triggers and queried by [`Db::search`].
 
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use sqlx::{Row, SqlitePool};
 
-use types::api::{APIError, APIResult, CompressedEvent};
-use types::timing::{TimeRange, Timing};
+use types::api::{APIError, APIResult, CompressedEvent, EventOrder, EventsPage, EventsRequest};
+use types::timing::{Marker, MarkersRequest, TimeRange, Timing};
 
 #[derive(Clone)]
 pub struct Db {
@@ -19,8 +23,9 @@ pub struct Db {
 }
 
 /// Internal row shape. Plugins never see this — they hand us a
-/// [`CompressedEvent`] + dedup id and we wrap it.
-#[derive(Debug, Clone)]
+/// [`CompressedEvent`] + dedup id and we wrap it. Also what `/ingest`
+/// accepts as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredEvent<T = serde_json::Value> {
     pub id: String,
     pub title: String,
@@ -59,6 +64,8 @@ impl Db {
             .execute(&pool)
             .await?;
 
+        create_search_index(&pool).await?;
+
         Ok(Db { pool })
     }
 
@@ -164,6 +171,139 @@ impl Db {
         Ok(out)
     }
 
+    /// One page of events overlapping `request.range`, ordered by start time
+    /// (ties by id). `next` continues after the page's last event, so events
+    /// stored meanwhile don't shift later pages.
+    pub async fn query_page(&self, request: &EventsRequest) -> Result<EventsPage, DbError> {
+        let start = request.range.start.timestamp_millis();
+        let end = request.range.end.timestamp_millis();
+        let after = match &request.cursor {
+            Some(c) => Some(parse_cursor(c).ok_or(DbError::InvalidCursor)?),
+            None => None,
+        };
+        let (cmp, dir) = match request.order {
+            EventOrder::Asc => (">", "ASC"),
+            EventOrder::Desc => ("<", "DESC"),
+        };
+        // One extra row tells whether there's another page. SQLite takes a
+        // negative limit as none.
+        let limit = request.limit.map_or(-1, |l| i64::from(l) + 1);
+
+        let rows = sqlx::query(&format!(
+            "SELECT id, start_ts, end_ts, title, data \
+             FROM events \
+             WHERE start_ts < ?1 AND end_ts >= ?2 \
+               AND (?3 IS NULL OR (start_ts, id) {cmp} (?3, ?4)) \
+             ORDER BY start_ts {dir}, id {dir} \
+             LIMIT ?5"
+        ))
+        .bind(end)
+        .bind(start)
+        .bind(after.as_ref().map(|(ts, _)| *ts))
+        .bind(after.as_ref().map(|(_, id)| id.as_str()))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let more = request.limit.is_some_and(|l| rows.len() > l as usize);
+        let mut page = EventsPage::default();
+        for row in rows
+            .iter()
+            .take(request.limit.map_or(usize::MAX, |l| l as usize))
+        {
+            let start_ts: i64 = row.try_get("start_ts")?;
+            let end_ts: i64 = row.try_get("end_ts")?;
+            let title: String = row.try_get("title")?;
+            let data: String = row.try_get("data")?;
+            let data: serde_json::Value = serde_json::from_str(&data)?;
+            let time = bounds_to_timing(start_ts, end_ts);
+            page.events.push(CompressedEvent { data, time, title });
+            if more {
+                let id: String = row.try_get("id")?;
+                page.next = Some(format!("{}:{}", start_ts, id));
+            }
+        }
+        Ok(page)
+    }
+
+    /// Event counts per bucket of `request`, in time order, without loading
+    /// any payloads. Range events count in every bucket they overlap; empty
+    /// buckets are left out.
+    pub async fn markers(&self, request: &MarkersRequest) -> Result<Vec<Marker>, DbError> {
+        let start = request.range.start.timestamp_millis();
+        let end = request.range.end.timestamp_millis();
+        let step = request.resolution.step().num_milliseconds();
+
+        // First and last bucket of each event, clamped to the range; range
+        // ends are exclusive. Events with the same pair are counted together,
+        // so instants collapse into one row per bucket.
+        let rows = sqlx::query(
+            "SELECT (MAX(start_ts, ?1) - ?1) / ?3 AS first, \
+                    (MIN(MAX(end_ts - 1, start_ts), ?2 - 1) - ?1) / ?3 AS last, \
+                    COUNT(*) AS amount \
+             FROM events \
+             WHERE start_ts < ?2 AND MAX(end_ts - 1, start_ts) >= ?1 \
+             GROUP BY first, last",
+        )
+        .bind(start)
+        .bind(end)
+        .bind(step)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut buckets: BTreeMap<i64, u32> = BTreeMap::new();
+        for row in rows {
+            let first: i64 = row.try_get("first")?;
+            let last: i64 = row.try_get("last")?;
+            let amount: i64 = row.try_get("amount")?;
+            for bucket in first..=last {
+                *buckets.entry(bucket).or_insert(0) += amount as u32;
+            }
+        }
+        Ok(buckets
+            .into_iter()
+            .filter_map(|(bucket, amount)| {
+                let time = chrono::DateTime::from_timestamp_millis(start + bucket * step)?;
+                Some(Marker {
+                    time,
+                    amount,
+                    plugins: Default::default(),
+                })
+            })
+            .collect())
+    }
+
+    /// Full-text search over titles and payloads, best matches first. Every
+    /// word in `query` must match, as a word prefix.
+    pub async fn search(&self, query: &str, limit: u32) -> Result<Vec<CompressedEvent>, DbError> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        let rows = sqlx::query(
+            "SELECT e.start_ts, e.end_ts, e.title, e.data \
+             FROM events_fts f JOIN events e ON e.rowid = f.rowid \
+             WHERE events_fts MATCH ? \
+             ORDER BY f.rank \
+             LIMIT ?",
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows {
+            let start_ts: i64 = row.try_get("start_ts")?;
+            let end_ts: i64 = row.try_get("end_ts")?;
+            let title: String = row.try_get("title")?;
+            let data: String = row.try_get("data")?;
+            let data: serde_json::Value = serde_json::from_str(&data)?;
+            let time = bounds_to_timing(start_ts, end_ts);
+            out.push(CompressedEvent { data, time, title });
+        }
+        Ok(out)
+    }
+
     /// Typed variant for plugins that want to read their own payloads back.
     pub async fn query_range_typed<T: DeserializeOwned>(
         &self,
@@ -202,6 +342,58 @@ impl Db {
     }
 }
 
+/// Create `events_fts` and its sync triggers. Databases from before the
+/// index existed get it filled once.
+async fn create_search_index(pool: &SqlitePool) -> Result<(), DbError> {
+    let exists: Option<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'events_fts'",
+    )
+    .fetch_optional(pool)
+    .await?;
+    if exists.is_some() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    for stmt in [
+        "CREATE VIRTUAL TABLE events_fts USING fts5(\
+           title, data, content = 'events', content_rowid = 'rowid')",
+        "CREATE TRIGGER events_fts_insert AFTER INSERT ON events BEGIN \
+           INSERT INTO events_fts(rowid, title, data) VALUES (new.rowid, new.title, new.data); \
+         END",
+        "CREATE TRIGGER events_fts_delete AFTER DELETE ON events BEGIN \
+           INSERT INTO events_fts(events_fts, rowid, title, data) \
+             VALUES ('delete', old.rowid, old.title, old.data); \
+         END",
+        "CREATE TRIGGER events_fts_update AFTER UPDATE ON events BEGIN \
+           INSERT INTO events_fts(events_fts, rowid, title, data) \
+             VALUES ('delete', old.rowid, old.title, old.data); \
+           INSERT INTO events_fts(rowid, title, data) VALUES (new.rowid, new.title, new.data); \
+         END",
+        "INSERT INTO events_fts(events_fts) VALUES ('rebuild')",
+    ] {
+        sqlx::query(stmt).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Turn free text into an FTS5 query: each word quoted (so FTS5 syntax in
+/// user input is taken literally) and prefix-matched. `None` if there are
+/// no words.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// `start_ts:id` of the event a page ended with.
+fn parse_cursor(cursor: &str) -> Option<(i64, String)> {
+    let (ts, id) = cursor.split_once(':')?;
+    Some((ts.parse().ok()?, id.to_string()))
+}
+
 fn timing_bounds(t: &Timing) -> (i64, i64) {
     match t {
         Timing::Instant(dt) => {
@@ -231,6 +423,8 @@ pub enum DbError {
     Io(#[from] std::io::Error),
     #[error("json: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("invalid cursor")]
+    InvalidCursor,
 }
 
 impl From<DbError> for APIError {
@@ -1,12 +1,17 @@
-//! Async error reporting. Fire-and-forget POST to the configured
-//! `error_report_url` (if any) plus a local log line. Matches the semantics
-//! of the old `server_api::error::error_string` helper.
+//! Async error reporting. Fire-and-forget GET to the configured
+//! `error_report_url` (if any), a report to the main server's error log (if
+//! `server_url` is set) and a local log line, each with the current
+//! `X-Request-Id` if there is one. Matches the semantics of the
+//! old `server_api::error::error_string` helper.
 
 use std::sync::Arc;
 
 use reqwest::Client;
 use url::Url;
 
+use crate::notify::Notifier;
+use crate::request_id;
+
 #[derive(Clone)]
 pub struct ErrorReporter {
     inner: Arc<Inner>,
@@ -16,6 +21,7 @@ struct Inner {
     plugin_name: String,
     url: Option<Url>,
     client: Client,
+    notifier: Option<Notifier>,
 }
 
 impl ErrorReporter {
@@ -25,20 +31,45 @@ impl ErrorReporter {
                 plugin_name: plugin_name.into(),
                 url,
                 client: Client::new(),
+                notifier: None,
             }),
         }
     }
 
+    /// Also send reports to the main server's error log through `notifier`.
+    pub fn with_notifier(self, notifier: Notifier) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                plugin_name: self.inner.plugin_name.clone(),
+                url: self.inner.url.clone(),
+                client: self.inner.client.clone(),
+                notifier: Some(notifier),
+            }),
+        }
+    }
+
+    /// Report `message`. Called while handling a request, the report
+    /// carries its `X-Request-Id`.
     pub fn report(&self, message: impl Into<String>) {
         let message = message.into();
+        let request_id = request_id::current();
+        tracing::error!(plugin = %self.inner.plugin_name, "{}", message);
+        if let Some(notifier) = &self.inner.notifier {
+            notifier.report_error(message.clone());
+        }
         let me = self.inner.clone();
         tokio::spawn(async move {
-            tracing::error!(plugin = %me.plugin_name, "{}", message);
             if let Some(url) = &me.url {
                 let mut url = url.clone();
-                url.query_pairs_mut()
-                    .append_pair("plugin", &me.plugin_name)
-                    .append_pair("error", &message);
+                {
+                    let mut query = url.query_pairs_mut();
+                    query
+                        .append_pair("plugin", &me.plugin_name)
+                        .append_pair("error", &message);
+                    if let Some(id) = &request_id {
+                        query.append_pair("request_id", id);
+                    }
+                }
                 if let Err(e) = me.client.get(url).send().await {
                     tracing::warn!(plugin = %me.plugin_name, "error webhook failed: {}", e);
                 }
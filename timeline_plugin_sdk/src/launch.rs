@@ -3,22 +3,28 @@
 use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::future::BoxFuture;
 use futures::FutureExt;
 use rocket::{routes, Build, Config as RocketConfig, Rocket, Route};
 
-use types::api::{APIResult, CompressedEvent};
+use types::api::{
+    APIResult, Capability, CompressedEvent, EventsPage, EventsRequest, PLUGIN_PROTOCOL_VERSION,
+};
 use types::timing::TimeRange;
 
 use crate::assets::AssetStore;
+use crate::auth::UrlSigner;
 use crate::cache::Cache;
 use crate::config::BaseConfig;
-use crate::db::Db;
+use crate::db::{Db, StoredEvent};
 use crate::error::ErrorReporter;
-use crate::manifest::Manifest;
+use crate::manifest::{Manifest, RouteRule, ServedManifest};
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
 use crate::plugin::{Context, Plugin};
+use crate::request_id;
 
 /// Type-erased plugin handle behind a trait object. Rocket state holds one
 /// of these so the standard routes can call into whichever concrete plugin
@@ -37,21 +43,72 @@ impl PluginHandle {
         self.inner.obj_manifest()
     }
 
+    /// The manifest plus the SDK-derived fields, as served on `GET /manifest`.
+    pub fn served_manifest(&self) -> ServedManifest {
+        ServedManifest {
+            manifest: self.inner.obj_manifest(),
+            routes: self.inner.obj_route_access(),
+            version: self.inner.obj_version(),
+            sdk_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            protocol_version: Some(PLUGIN_PROTOCOL_VERSION),
+            capabilities: self.capabilities(),
+        }
+    }
+
+    /// What the SDK serves for this plugin beyond `/events`. `/markers` and
+    /// `/search` work off the [`Db`], so they count only if events are kept
+    /// there.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = vec![Capability::EventsPaging];
+        if self.events_from_db() {
+            capabilities.push(Capability::Markers);
+        }
+        if self.events_from_db() || self.accepts_ingest() {
+            capabilities.push(Capability::Search);
+        }
+        if self.accepts_ingest() {
+            capabilities.push(Capability::Ingest);
+        }
+        capabilities
+    }
+
     pub async fn events(&self, range: TimeRange) -> APIResult<Vec<CompressedEvent>> {
         self.inner.obj_events(range).await
     }
+
+    pub async fn events_page(&self, request: EventsRequest) -> APIResult<EventsPage> {
+        self.inner.obj_events_page(request).await
+    }
+
+    pub fn events_from_db(&self) -> bool {
+        self.inner.obj_events_from_db()
+    }
+
+    pub fn accepts_ingest(&self) -> bool {
+        self.inner.obj_accepts_ingest()
+    }
+
+    pub async fn ingest(&self, payload: serde_json::Value) -> APIResult<Vec<StoredEvent>> {
+        self.inner.obj_ingest(payload).await
+    }
 }
 
 /// Ambient state every plugin route can look up:
 ///  - the bearer token (for the auth guard)
 ///  - asset store (for `/assets/<path..>`)
+///  - notifier (to announce events stored by `/ingest`)
+///  - metrics (for `/metrics`)
 pub struct PluginState {
     pub token: String,
+    /// Checks signed URLs (for [`SignedUrl`](crate::auth::SignedUrl)).
+    pub signer: UrlSigner,
     pub plugin_name: String,
     pub assets: AssetStore,
     pub db: Db,
     pub cache: Cache,
     pub errors: ErrorReporter,
+    pub notifier: Notifier,
+    pub metrics: Metrics,
 }
 
 /// Entry point. A plugin's `main` is typically just:
@@ -71,67 +128,111 @@ pub async fn launch<P: Plugin>(config_path: impl Into<PathBuf>) -> anyhow::Resul
     let db = Db::open(cfg.plugin.db_path()).await?;
     let assets = AssetStore::open(cfg.plugin.assets_root()).await?;
     let cache = Cache::open(cfg.plugin.cache_root()).await?;
-    let errors = ErrorReporter::new(cfg.plugin.name.clone(), cfg.plugin.error_report_url.clone());
+    let notifier = Notifier::new(
+        cfg.plugin.name.clone(),
+        cfg.plugin.server_url.clone(),
+        cfg.plugin.token.clone(),
+    );
+    let errors = ErrorReporter::new(cfg.plugin.name.clone(), cfg.plugin.error_report_url.clone())
+        .with_notifier(notifier.clone());
 
+    let signer = UrlSigner::new(cfg.plugin.name.clone(), cfg.plugin.token.clone());
     let ctx = Context {
         config: cfg.plugin.clone(),
+        signer: signer.clone(),
         extra: cfg.config.clone(),
         db: db.clone(),
         assets: assets.clone(),
         cache: cache.clone(),
         errors: errors.clone(),
+        notifier: notifier.clone(),
     };
 
     let plugin = Arc::new(P::new(ctx).await?);
-    let handle = PluginHandle::new(plugin.clone());
+    let metrics = Metrics::new(&cfg.plugin.name);
 
-    spawn_request_loop(plugin.clone(), cfg.plugin.name.clone(), errors.clone());
+    spawn_request_loop(
+        plugin.clone(),
+        cfg.plugin.name.clone(),
+        errors.clone(),
+        metrics.clone(),
+    );
 
     let state = PluginState {
         token: cfg.plugin.token.clone(),
+        signer,
         plugin_name: cfg.plugin.name.clone(),
         assets,
         db,
         cache,
         errors,
+        notifier,
+        metrics,
     };
 
-    let plugin_routes = plugin.obj_routes();
-
     let rocket_cfg = RocketConfig::figment().merge(("port", cfg.plugin.port));
+    build(rocket::custom(rocket_cfg), plugin, state)
+        .launch()
+        .await?;
+    Ok(())
+}
 
-    let mut rocket = rocket::custom(rocket_cfg)
+/// Mount the standard and plugin routes on `rocket` and hand it to
+/// [`Plugin::rocket_attach`].
+fn build<P: Plugin>(rocket: Rocket<Build>, plugin: Arc<P>, state: PluginState) -> Rocket<Build> {
+    let handle = PluginHandle::new(plugin.clone());
+    let metrics = state.metrics.clone();
+    let plugin_routes = plugin.obj_routes();
+
+    let mut rocket = rocket
         .manage(state)
         .manage(handle)
+        .attach(metrics)
+        .attach(request_id::Tag)
         .mount(
             "/",
-            routes![
+            request_id::scoped(routes![
                 crate::routes::events,
+                crate::routes::markers,
+                crate::routes::search,
+                crate::routes::ingest,
                 crate::routes::manifest,
                 crate::routes::health,
+                crate::routes::metrics,
                 crate::routes::assets,
-            ],
+            ]),
         );
 
     if !plugin_routes.is_empty() {
-        rocket = rocket.mount("/", plugin_routes);
+        rocket = rocket.mount("/", request_id::scoped(plugin_routes));
     }
 
-    rocket = plugin.obj_rocket_attach(rocket);
-
-    rocket.launch().await?;
-    Ok(())
+    plugin.obj_rocket_attach(rocket)
 }
 
-fn spawn_request_loop<P: Plugin>(plugin: Arc<P>, name: String, errors: ErrorReporter) {
+fn spawn_request_loop<P: Plugin>(
+    plugin: Arc<P>,
+    name: String,
+    errors: ErrorReporter,
+    metrics: Metrics,
+) {
     tokio::spawn(async move {
         loop {
             let fut = plugin.obj_request_loop();
+            let started = Instant::now();
             let outcome = AssertUnwindSafe(fut).catch_unwind().await;
+            let took = started.elapsed();
             match outcome {
-                Ok(Some(d)) => tokio::time::sleep(d).await,
-                Ok(None) => break,
+                Ok(Some(d)) => {
+                    metrics.observe_request_loop("ok", took);
+                    tokio::time::sleep(d).await
+                }
+                Ok(None) => {
+                    metrics.observe_request_loop("stopped", took);
+                    break;
+                }
                 Err(panic) => {
+                    metrics.observe_request_loop("panicked", took);
                     let msg = panic_message(&panic);
                     errors.report(format!("{} request_loop panicked: {}", name, msg));
                     tokio::time::sleep(Duration::from_secs(300)).await;
@@ -155,9 +256,22 @@ fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
 
 trait PluginObj: Send + Sync + 'static {
     fn obj_manifest(&self) -> Manifest;
-    fn obj_events<'a>(&'a self, range: TimeRange) -> BoxFuture<'a, APIResult<Vec<CompressedEvent>>>;
+    fn obj_events<'a>(&'a self, range: TimeRange)
+        -> BoxFuture<'a, APIResult<Vec<CompressedEvent>>>;
+    fn obj_events_page<'a>(
+        &'a self,
+        request: EventsRequest,
+    ) -> BoxFuture<'a, APIResult<EventsPage>>;
     fn obj_request_loop<'a>(&'a self) -> BoxFuture<'a, Option<Duration>>;
     fn obj_routes(&self) -> Vec<Route>;
+    fn obj_route_access(&self) -> Vec<RouteRule>;
+    fn obj_version(&self) -> Option<String>;
+    fn obj_events_from_db(&self) -> bool;
+    fn obj_accepts_ingest(&self) -> bool;
+    fn obj_ingest<'a>(
+        &'a self,
+        payload: serde_json::Value,
+    ) -> BoxFuture<'a, APIResult<Vec<StoredEvent>>>;
     fn obj_rocket_attach(&self, rocket: Rocket<Build>) -> Rocket<Build>;
 }
 
@@ -165,16 +279,116 @@ impl<P: Plugin> PluginObj for P {
     fn obj_manifest(&self) -> Manifest {
         Plugin::manifest(self)
     }
-    fn obj_events<'a>(&'a self, range: TimeRange) -> BoxFuture<'a, APIResult<Vec<CompressedEvent>>> {
+    fn obj_events<'a>(
+        &'a self,
+        range: TimeRange,
+    ) -> BoxFuture<'a, APIResult<Vec<CompressedEvent>>> {
         Box::pin(Plugin::events(self, range))
     }
+    fn obj_events_page<'a>(
+        &'a self,
+        request: EventsRequest,
+    ) -> BoxFuture<'a, APIResult<EventsPage>> {
+        Box::pin(Plugin::events_page(self, request))
+    }
     fn obj_request_loop<'a>(&'a self) -> BoxFuture<'a, Option<Duration>> {
         Box::pin(Plugin::request_loop(self))
     }
     fn obj_routes(&self) -> Vec<Route> {
         Plugin::routes(self)
     }
+    fn obj_route_access(&self) -> Vec<RouteRule> {
+        Plugin::route_access(self)
+    }
+    fn obj_version(&self) -> Option<String> {
+        Plugin::version(self)
+    }
+    fn obj_events_from_db(&self) -> bool {
+        Plugin::events_from_db(self)
+    }
+    fn obj_accepts_ingest(&self) -> bool {
+        Plugin::accepts_ingest(self)
+    }
+    fn obj_ingest<'a>(
+        &'a self,
+        payload: serde_json::Value,
+    ) -> BoxFuture<'a, APIResult<Vec<StoredEvent>>> {
+        Box::pin(Plugin::ingest(self, payload))
+    }
     fn obj_rocket_attach(&self, rocket: Rocket<Build>) -> Rocket<Build> {
         Plugin::rocket_attach(self, rocket)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::asynchronous::Client;
+
+    use super::*;
+    use crate::manifest::Style;
+
+    pub const TOKEN: &str = "test-token";
+
+    /// Serves events it keeps itself, like a plugin reading a remote API.
+    struct InMemory;
+
+    impl Plugin for InMemory {
+        async fn new(_ctx: Context) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                name: "in_memory".to_string(),
+                display_name: "In memory".to_string(),
+                style: Style::Acc1,
+                icon: None,
+                web_entry: None,
+            }
+        }
+
+        async fn events(&self, _range: TimeRange) -> APIResult<Vec<CompressedEvent>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// `plugin` mounted the way [`launch`] does, with its state under `dir`.
+    pub async fn client<P: Plugin>(plugin: P, dir: &Path) -> Client {
+        let name = plugin.manifest().name;
+        let state = PluginState {
+            token: TOKEN.to_string(),
+            signer: UrlSigner::new(name.clone(), TOKEN),
+            plugin_name: name.clone(),
+            assets: AssetStore::open(dir.join("assets")).await.unwrap(),
+            db: Db::open(dir.join("events.sqlite")).await.unwrap(),
+            cache: Cache::open(dir.join("cache")).await.unwrap(),
+            errors: ErrorReporter::new(name.clone(), None),
+            notifier: Notifier::new(name.clone(), None, TOKEN),
+            metrics: Metrics::new(&name),
+        };
+        Client::untracked(build(rocket::build(), Arc::new(plugin), state))
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn events_outside_the_db_are_left_to_the_server_to_count() {
+        let capabilities = PluginHandle::new(Arc::new(InMemory)).capabilities();
+        assert_eq!(capabilities, vec![Capability::EventsPaging]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(InMemory, dir.path()).await;
+        let res = client
+            .post("/markers")
+            .header(Header::new("Authorization", format!("Bearer {}", TOKEN)))
+            .header(ContentType::JSON)
+            .body(r#"{"start":"2026-01-01T00:00:00Z","end":"2026-01-02T00:00:00Z"}"#)
+            .dispatch()
+            .await;
+        // The main server's cue to count `/events` itself.
+        assert_eq!(res.status(), Status::NotFound);
+    }
+}
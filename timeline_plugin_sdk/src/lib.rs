@@ -4,8 +4,8 @@
 //! server talks to over HTTP. Every plugin author implements the [`Plugin`]
 //! trait and calls [`launch`] from `main`; the SDK takes care of config
 //! loading, SQLite-backed event storage, asset storage, bearer-token auth,
-//! and the standard HTTP contract (`/events`, `/manifest`, `/assets/<path>`,
-//! `/health`).
+//! and the standard HTTP contract (`/events`, `/markers`, `/search`,
+//! `/ingest`, `/manifest`, `/assets/<path>`, `/health`, `/metrics`).
 
 pub mod assets;
 pub mod auth;
@@ -15,16 +15,22 @@ pub mod db;
 pub mod error;
 pub mod launch;
 pub mod manifest;
+pub mod metrics;
+pub mod notify;
 pub mod plugin;
+pub mod request_id;
 pub mod routes;
 
 pub use assets::AssetStore;
+pub use auth::{AuthedClient, SignedUrl, UrlSigner};
 pub use cache::Cache;
 pub use config::{BaseConfig, PluginConfig};
 pub use db::{Db, StoredEvent};
 pub use error::ErrorReporter;
 pub use launch::launch;
-pub use manifest::{Manifest, Style};
+pub use manifest::{Manifest, RouteAccess, RouteRule, ServedManifest, Style};
+pub use metrics::Metrics;
+pub use notify::Notifier;
 pub use plugin::{Context, Plugin};
 
 pub use types::api::{APIError, APIResult, CompressedEvent};
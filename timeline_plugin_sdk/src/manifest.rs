@@ -3,6 +3,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use types::api::Capability;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Manifest {
     pub name: String,
@@ -20,6 +22,68 @@ pub struct Manifest {
     pub web_entry: Option<String>,
 }
 
+/// What `GET /manifest` actually serves: the plugin's own [`Manifest`] plus
+/// the fields the SDK fills in from the [`Plugin`](crate::Plugin) impl.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServedManifest {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    /// From [`Plugin::route_access`](crate::Plugin::route_access).
+    #[serde(default)]
+    pub routes: Vec<RouteRule>,
+    /// From [`Plugin::version`](crate::Plugin::version).
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Version of this SDK the plugin was built with.
+    #[serde(default)]
+    pub sdk_version: Option<String>,
+    /// [`PLUGIN_PROTOCOL_VERSION`](types::api::PLUGIN_PROTOCOL_VERSION) of
+    /// this SDK.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// What the plugin implements beyond `/events`, from the
+    /// [`Plugin`](crate::Plugin) impl.
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+/// Marks plugin routes the main server may proxy for callers without a
+/// timeline login. Anything not covered by a rule stays private.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// Path prefix relative to the plugin root, matched in whole segments:
+    /// `"file"` covers `/api/plugin/<name>/file/...` but not `filesecret/...`.
+    pub prefix: String,
+    pub access: RouteAccess,
+}
+
+impl RouteRule {
+    pub fn public(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            access: RouteAccess::Public,
+        }
+    }
+
+    pub fn signed(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            access: RouteAccess::Signed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteAccess {
+    /// Anyone may call it; the main server still attaches the bearer token.
+    Public,
+    /// Signed-URL-only. Anonymous callers are forwarded *without* the bearer
+    /// token, so the route takes [`SignedUrl`](crate::auth::SignedUrl)
+    /// instead of [`AuthedClient`](crate::auth::AuthedClient).
+    Signed,
+}
+
 /// Mirrors the old `client_api::style::Style` enum. Preserves the CSS-variable
 /// mapping so the main frontend can keep using `var(--accentColor1)` etc.
 #[derive(Debug, Clone, Serialize, Deserialize)]
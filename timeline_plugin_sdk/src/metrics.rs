@@ -0,0 +1,115 @@
+//! Prometheus metrics served on `GET /metrics` (bearer-token protected like
+//! the other standard routes). Every series carries a `plugin` label with
+//! the plugin's name.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntGauge, Opts, Registry, TextEncoder};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Handled requests by method, route (as declared, e.g.
+    /// `/assets/<path..>`) and status.
+    pub requests: HistogramVec,
+    /// Runs of [`Plugin::request_loop`](crate::Plugin::request_loop) by
+    /// outcome: `ok`, `stopped` (returned `None`) or `panicked`.
+    pub request_loop: HistogramVec,
+    /// Rows in the plugin's [`Db`](crate::Db), refreshed on every scrape.
+    pub db_events: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(plugin_name: &str) -> Self {
+        let labels = HashMap::from([("plugin".to_string(), plugin_name.to_string())]);
+        let registry = Registry::new_custom(Some("timeline_plugin".to_string()), Some(labels))
+            .expect("metrics registry");
+        let requests = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to answer requests",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("metric");
+        let request_loop = HistogramVec::new(
+            HistogramOpts::new(
+                "request_loop_duration_seconds",
+                "Time taken by request_loop runs",
+            )
+            .buckets(vec![0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0]),
+            &["outcome"],
+        )
+        .expect("metric");
+        let db_events = IntGauge::with_opts(Opts::new(
+            "db_events",
+            "Events stored in the plugin's database",
+        ))
+        .expect("metric");
+        registry
+            .register(Box::new(requests.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(request_loop.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(db_events.clone()))
+            .expect("register metric");
+        Self {
+            registry,
+            requests,
+            request_loop,
+            db_events,
+        }
+    }
+
+    pub fn observe_request_loop(&self, outcome: &str, took: Duration) {
+        self.request_loop
+            .with_label_values(&[outcome])
+            .observe(took.as_secs_f64());
+    }
+
+    /// Everything, in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut out)
+            .expect("encode metrics");
+        String::from_utf8(out).unwrap_or_default()
+    }
+}
+
+/// When the request arrived, kept in the request's local cache.
+struct RequestStart(Instant);
+
+#[rocket::async_trait]
+impl Fairing for Metrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let started = req.local_cache(|| RequestStart(Instant::now())).0;
+        let route = req
+            .route()
+            .map(|r| r.uri.path().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        self.requests
+            .with_label_values(&[
+                req.method().as_str(),
+                &route,
+                &res.status().code.to_string(),
+            ])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,115 @@
+//! Calls from the plugin back to the main timeline server. Fire-and-forget
+//! like [`ErrorReporter`](crate::ErrorReporter): failures are logged, and
+//! without a `server_url` every call is a no-op.
+
+use std::sync::Arc;
+
+use reqwest::Client;
+use url::Url;
+
+use types::api::{CompressedEvent, ErrorReportRequest, LiveEvents};
+use types::timing::TimeRange;
+
+use crate::db::StoredEvent;
+use crate::request_id;
+
+#[derive(Clone)]
+pub struct Notifier {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    plugin_name: String,
+    server_url: Option<Url>,
+    token: String,
+    client: Client,
+}
+
+impl Notifier {
+    pub fn new(
+        plugin_name: impl Into<String>,
+        server_url: Option<Url>,
+        token: impl Into<String>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                plugin_name: plugin_name.into(),
+                server_url,
+                token: token.into(),
+                client: Client::new(),
+            }),
+        }
+    }
+
+    /// Tell the server that events in `range` changed, so it drops what it
+    /// cached for that range. Call after ingesting or deleting events.
+    pub fn invalidate(&self, range: TimeRange) {
+        self.post("api/cache/invalidate", range);
+    }
+
+    /// Tell the server about events just stored with
+    /// [`Db::upsert`](crate::Db::upsert) / [`Db::upsert_many`](crate::Db::upsert_many).
+    /// Open timelines show them right away, and the server drops what it
+    /// cached for their time span (no separate [`invalidate`](Self::invalidate)
+    /// needed).
+    pub fn announce<T: serde::Serialize>(&self, events: &[StoredEvent<T>]) {
+        if events.is_empty() || self.inner.server_url.is_none() {
+            return;
+        }
+        let events = events
+            .iter()
+            .filter_map(|e| {
+                Some(CompressedEvent {
+                    data: serde_json::to_value(&e.data).ok()?,
+                    time: e.time.clone(),
+                    title: e.title.clone(),
+                })
+            })
+            .collect();
+        self.post(
+            "api/live/announce",
+            LiveEvents {
+                plugin: self.inner.plugin_name.clone(),
+                events,
+            },
+        );
+    }
+
+    /// Add `message` to the server's error log (`/api/errors/report`).
+    /// [`ErrorReporter::report`](crate::ErrorReporter::report) does this for
+    /// you.
+    pub fn report_error(&self, message: impl Into<String>) {
+        self.post(
+            "api/errors/report",
+            ErrorReportRequest {
+                message: message.into(),
+                request_id: request_id::current(),
+            },
+        );
+    }
+
+    fn post<T: serde::Serialize + Send + 'static>(&self, path: &'static str, body: T) {
+        let me = self.inner.clone();
+        let Some(base) = me.server_url.clone() else {
+            return;
+        };
+        let request_id = request_id::current();
+        tokio::spawn(async move {
+            let url = match base.join(path) {
+                Ok(u) => u,
+                Err(e) => {
+                    tracing::warn!(plugin = %me.plugin_name, "bad server_url: {}", e);
+                    return;
+                }
+            };
+            let mut req = me.client.post(url).bearer_auth(&me.token).json(&body);
+            if let Some(id) = request_id {
+                req = req.header(request_id::HEADER, id);
+            }
+            let res = req.send().await.and_then(|r| r.error_for_status());
+            if let Err(e) = res {
+                tracing::warn!(plugin = %me.plugin_name, "{} failed: {}", path, e);
+            }
+        });
+    }
+}
@@ -4,26 +4,32 @@ use std::time::Duration;
 
 use rocket::{Build, Rocket, Route};
 
-use types::api::{APIResult, CompressedEvent};
+use types::api::{APIError, APIResult, CompressedEvent, EventOrder, EventsPage, EventsRequest};
 use types::timing::TimeRange;
 
 use crate::assets::AssetStore;
+use crate::auth::UrlSigner;
 use crate::cache::Cache;
 use crate::config::PluginConfig;
-use crate::db::Db;
+use crate::db::{Db, StoredEvent};
 use crate::error::ErrorReporter;
-use crate::manifest::Manifest;
+use crate::manifest::{Manifest, RouteRule};
+use crate::notify::Notifier;
 
 /// Passed to [`Plugin::new`] once at startup. Gives the plugin everything the
 /// SDK set up on its behalf.
 pub struct Context {
     pub config: PluginConfig,
+    /// Signs links to routes marked [`RouteRule::signed`].
+    pub signer: UrlSigner,
     /// Plugin-specific sub-config extracted from the `[config]` table.
     pub extra: toml::Value,
     pub db: Db,
     pub assets: AssetStore,
     pub cache: Cache,
     pub errors: ErrorReporter,
+    /// Calls back into the main server, e.g. to announce newly stored events.
+    pub notifier: Notifier,
 }
 
 /// A timeline plugin. Implementations run in their own process.
@@ -42,6 +48,20 @@ pub trait Plugin: Sized + Send + Sync + 'static {
         range: TimeRange,
     ) -> impl std::future::Future<Output = APIResult<Vec<CompressedEvent>>> + Send;
 
+    /// One page of events for an `/events` request with a `limit` or
+    /// `cursor`. The default pages through [`events`](Self::events) in
+    /// memory; plugins serving straight from [`Context::db`] can return
+    /// `ctx.db.query_page(&request)` instead and skip loading the rest.
+    fn events_page(
+        &self,
+        request: EventsRequest,
+    ) -> impl std::future::Future<Output = APIResult<EventsPage>> + Send {
+        async move {
+            let events = self.events(request.range.clone()).await?;
+            page_in_memory(events, &request)
+        }
+    }
+
     /// Optional background loop. The SDK re-runs this after each returned
     /// `Duration`; return `None` to stop. Panics are caught by the SDK and
     /// reported via [`ErrorReporter`].
@@ -56,6 +76,46 @@ pub trait Plugin: Sized + Send + Sync + 'static {
         Vec::new()
     }
 
+    /// Access rules for routes from [`Plugin::routes`] that must work without
+    /// a timeline login (e.g. signed file links). The main server only
+    /// proxies unmatched paths for authenticated users. Signed routes take
+    /// the [`SignedUrl`](crate::auth::SignedUrl) guard and hand out links
+    /// from [`Context::signer`].
+    fn route_access(&self) -> Vec<RouteRule> {
+        Vec::new()
+    }
+
+    /// Whether the SDK's `POST /ingest` is on. Events pushed there are
+    /// stored in [`Context::db`] and announced; the plugin only sees them
+    /// through [`ingest`](Self::ingest).
+    fn accepts_ingest(&self) -> bool {
+        false
+    }
+
+    /// Map a body POSTed to `/ingest` into events to store. The default
+    /// takes one [`StoredEvent`] or a list of them; override to accept a
+    /// webhook's own payload shape.
+    fn ingest(
+        &self,
+        payload: serde_json::Value,
+    ) -> impl std::future::Future<Output = APIResult<Vec<StoredEvent>>> + Send {
+        async move { stored_events(payload) }
+    }
+
+    /// Whether [`events`](Self::events) returns what's stored in
+    /// [`Context::db`]. Return `true` to have `/markers` count events with a
+    /// SQL query there and `/search` search them; otherwise the main server
+    /// counts `/events` results and leaves the plugin out of searches.
+    fn events_from_db(&self) -> bool {
+        false
+    }
+
+    /// The plugin's version, reported in its manifest and on the main
+    /// server's `/api/status`. Usually `Some(env!("CARGO_PKG_VERSION").into())`.
+    fn version(&self) -> Option<String> {
+        None
+    }
+
     /// Attach plugin-specific Rocket state (e.g. via `rocket.manage(...)`).
     /// Called once during launch, after the SDK has already managed its own
     /// `PluginState` and `PluginHandle`. Default is identity.
@@ -63,3 +123,41 @@ pub trait Plugin: Sized + Send + Sync + 'static {
         rocket
     }
 }
+
+/// Sort `events` by start time and cut out the page `request` asks for. The
+/// cursor is the offset of the next page.
+fn page_in_memory(
+    mut events: Vec<CompressedEvent>,
+    request: &EventsRequest,
+) -> APIResult<EventsPage> {
+    let offset = match &request.cursor {
+        Some(c) => c
+            .parse::<usize>()
+            .map_err(|_| APIError::Custom("invalid cursor".to_string()))?,
+        None => 0,
+    };
+    events.sort_by(|a, b| a.time.cmp(&b.time));
+    if request.order == EventOrder::Desc {
+        events.reverse();
+    }
+    let total = events.len();
+    let from = offset.min(total);
+    let to = request
+        .limit
+        .map_or(total, |l| from.saturating_add(l as usize).min(total));
+    events.truncate(to);
+    events.drain(..from);
+    Ok(EventsPage {
+        events,
+        next: (to < total).then(|| to.to_string()),
+    })
+}
+
+/// One event or a list of them, as sent to `/ingest`.
+fn stored_events(payload: serde_json::Value) -> APIResult<Vec<StoredEvent>> {
+    if payload.is_array() {
+        Ok(serde_json::from_value(payload)?)
+    } else {
+        Ok(vec![serde_json::from_value(payload)?])
+    }
+}
@@ -0,0 +1,83 @@
+//! `X-Request-Id` from the main server. Every route (the standard ones and
+//! those from [`Plugin::routes`](crate::Plugin::routes)) runs inside a
+//! `request{id=..}` tracing span, and [`ErrorReporter`](crate::ErrorReporter)
+//! and [`Notifier`](crate::Notifier) calls made while handling it carry the
+//! same id, so the plugin's log lines match the server's.
+
+use std::future::Future;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::route::{Handler, Outcome};
+use rocket::{Data, Request, Response, Route};
+use tracing::Instrument;
+
+use types::request_id::accept_or_generate;
+pub use types::request_id::HEADER;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// The id of the request being handled, if any. Read it before spawning:
+/// spawned tasks don't inherit it.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Run `fut` as part of request `id`: in its span, with [`current`] set.
+pub async fn scope<F: Future>(id: String, fut: F) -> F::Output {
+    let span = tracing::info_span!("request", id = %id);
+    CURRENT.scope(id, fut).instrument(span).await
+}
+
+/// `req`'s id: the main server's, or a new one for direct calls.
+pub fn of<'r>(req: &'r Request<'_>) -> &'r str {
+    &req.local_cache(|| {
+        let incoming = req.headers().get_one(HEADER);
+        RequestId(accept_or_generate(incoming, || {
+            uuid::Uuid::new_v4().into_bytes()
+        }))
+    })
+    .0
+}
+
+struct RequestId(String);
+
+/// `routes` with each handler run inside its request's [`scope`].
+pub(crate) fn scoped(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Scoped(route.handler));
+            route
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Scoped(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Scoped {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        scope(of(req).to_string(), self.0.handle(req, data)).await
+    }
+}
+
+/// Echoes the id on every response.
+pub(crate) struct Tag;
+
+#[rocket::async_trait]
+impl Fairing for Tag {
+    fn info(&self) -> Info {
+        Info {
+            name: "X-Request-Id",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        res.set_header(Header::new(HEADER, of(req).to_string()));
+    }
+}
@@ -1,36 +1,167 @@
 //! Standard HTTP endpoints every plugin exposes.
 //!
-//! `/events`, `/manifest`, `/health`, and `/assets/<path..>` are identical
-//! across every plugin. Plugin-specific routes come from [`Plugin::routes`]
-//! and are mounted alongside.
+//! `/events`, `/markers`, `/search`, `/ingest`, `/manifest`, `/health`,
+//! `/metrics` and `/assets/<path..>` are identical across every plugin. Plugin-specific
+//! routes come from [`Plugin::routes`] and are mounted alongside.
 
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::PathBuf;
 
 use rocket::fs::NamedFile;
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, content::RawJson, Responder, Response};
 use rocket::serde::json::Json;
-use rocket::{get, post, State};
+use rocket::{get, post, Request, State};
+use serde::Serialize;
 
 use crate::auth::AuthedClient;
+use crate::db::StoredEvent;
 use crate::launch::{PluginHandle, PluginState};
-use types::api::{APIError, APIResult, CompressedEvent};
-use types::timing::TimeRange;
+use types::api::{APIError, APIResult, CompressedEvent, EventsPage, EventsRequest, SearchRequest};
+use types::timing::{Marker, MarkersRequest, Timing, MAX_MARKER_BUCKETS};
 
-#[post("/events", data = "<range>")]
+#[post("/events", data = "<request>")]
 pub async fn events(
     _auth: AuthedClient,
-    range: Json<TimeRange>,
+    request: Json<EventsRequest>,
     handle: &State<PluginHandle>,
+) -> Json<APIResult<EventsReply>> {
+    let request = request.into_inner();
+    if request.is_paged() {
+        Json(handle.events_page(request).await.map(EventsReply::Page))
+    } else {
+        Json(handle.events(request.range).await.map(EventsReply::All))
+    }
+}
+
+/// `/events` answers a bare range with the plain list, as before paging.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum EventsReply {
+    All(Vec<CompressedEvent>),
+    Page(EventsPage),
+}
+
+/// Event counts per time bucket, from the plugin's [`Db`](crate::Db). 404 if
+/// [`Plugin::events_from_db`](crate::Plugin::events_from_db) says the Db
+/// isn't where events come from; the main server then counts `/events`.
+#[post("/markers", data = "<request>")]
+pub async fn markers(
+    _auth: AuthedClient,
+    request: Json<MarkersRequest>,
+    handle: &State<PluginHandle>,
+    state: &State<PluginState>,
+) -> Result<Json<APIResult<Vec<Marker>>>, Status> {
+    if !handle.events_from_db() {
+        return Err(Status::NotFound);
+    }
+    if request.bucket_count() > MAX_MARKER_BUCKETS {
+        return Ok(Json(Err(APIError::Custom(format!(
+            "at most {} buckets allowed",
+            MAX_MARKER_BUCKETS
+        )))));
+    }
+    Ok(Json(crate::db::to_api_result(
+        state.db.markers(&request).await,
+    )))
+}
+
+/// Full-text search over the plugin's [`Db`](crate::Db).
+#[post("/search", data = "<request>")]
+pub async fn search(
+    _auth: AuthedClient,
+    request: Json<SearchRequest>,
+    state: &State<PluginState>,
 ) -> Json<APIResult<Vec<CompressedEvent>>> {
-    Json(handle.events(range.into_inner()).await)
+    let limit = request.limit.unwrap_or(50).min(500);
+    Json(crate::db::to_api_result(
+        state.db.search(&request.query, limit).await,
+    ))
+}
+
+/// Most events one `/ingest` call may store.
+pub const MAX_INGEST_BATCH: usize = 1000;
+
+/// Store events pushed by a webhook or script; answers how many were
+/// stored. 404 unless [`Plugin::accepts_ingest`](crate::Plugin::accepts_ingest).
+/// From outside, reach it through the main server's
+/// `/api/plugin/<name>/ingest` with a `proxy_write` API token.
+#[post("/ingest", data = "<payload>")]
+pub async fn ingest(
+    _auth: AuthedClient,
+    payload: Json<serde_json::Value>,
+    handle: &State<PluginHandle>,
+    state: &State<PluginState>,
+) -> (Status, Json<APIResult<usize>>) {
+    if !handle.accepts_ingest() {
+        let e = APIError::Custom(format!("{} doesn't accept ingest", state.plugin_name));
+        return (Status::NotFound, Json(Err(e)));
+    }
+    let events = match handle.ingest(payload.into_inner()).await {
+        Ok(events) => events,
+        Err(e) => return (Status::BadRequest, Json(Err(e))),
+    };
+    if let Err(e) = validate_ingest(&events) {
+        return (Status::BadRequest, Json(Err(APIError::Custom(e))));
+    }
+    if let Err(e) = state.db.upsert_many(&events).await {
+        return (Status::InternalServerError, Json(Err(e.into())));
+    }
+    state.notifier.announce(&events);
+    (Status::Ok, Json(Ok(events.len())))
+}
+
+fn validate_ingest(events: &[StoredEvent]) -> Result<(), String> {
+    if events.len() > MAX_INGEST_BATCH {
+        return Err(format!("at most {} events per request", MAX_INGEST_BATCH));
+    }
+    for (i, event) in events.iter().enumerate() {
+        if event.id.trim().is_empty() {
+            return Err(format!("event {}: empty id", i));
+        }
+        if event.title.trim().is_empty() {
+            return Err(format!("event {}: empty title", i));
+        }
+        if let Timing::Range(r) = &event.time {
+            if r.end < r.start {
+                return Err(format!("event {}: ends before it starts", i));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[get("/manifest")]
 pub async fn manifest(
     _auth: AuthedClient,
     handle: &State<PluginHandle>,
-) -> Json<crate::manifest::Manifest> {
-    Json(handle.manifest())
+) -> Tagged<crate::manifest::ServedManifest> {
+    Tagged(handle.served_manifest())
+}
+
+/// JSON with an `ETag` of its content; `304` to a matching `If-None-Match`,
+/// so the main server can revalidate its copy cheaply.
+pub struct Tagged<T>(pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Tagged<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_string(&self.0).map_err(|_| Status::InternalServerError)?;
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let etag = format!("\"{:016x}\"", hasher.finish());
+        let fresh = req
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim() == etag);
+        let mut res = if fresh {
+            Response::build().status(Status::NotModified).finalize()
+        } else {
+            RawJson(body).respond_to(req)?
+        };
+        res.set_raw_header("ETag", etag);
+        Ok(res)
+    }
 }
 
 #[get("/health")]
@@ -38,6 +169,16 @@ pub async fn health() -> &'static str {
     "ok"
 }
 
+/// Prometheus metrics; see [`Metrics`](crate::Metrics).
+#[get("/metrics")]
+pub async fn metrics(_auth: AuthedClient, state: &State<PluginState>) -> (ContentType, String) {
+    match state.db.count().await {
+        Ok(count) => state.metrics.db_events.set(count),
+        Err(e) => tracing::warn!(plugin = %state.plugin_name, "counting events failed: {}", e),
+    }
+    (ContentType::Plain, state.metrics.render())
+}
+
 #[get("/assets/<path..>")]
 pub async fn assets(
     _auth: AuthedClient,
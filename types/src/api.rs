@@ -1,8 +1,44 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
 pub type APIResult<T> = Result<T, APIError>;
 
+/// Version of the plugin HTTP contract (`/events`, `/manifest`, ...) this
+/// build speaks. Bumped only for breaking changes; a plugin declaring a
+/// different one in its manifest is rejected.
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+
+/// An optional part of the plugin protocol, listed in a plugin's manifest
+/// when it implements it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `/events` answers `limit`/`cursor` requests with pages.
+    EventsPaging,
+    /// `/markers` counts events itself.
+    Markers,
+    /// `/search` finds events.
+    Search,
+    /// `/ingest` accepts pushed events.
+    Ingest,
+    /// Declared by a newer plugin; ignored.
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EventsPaging => write!(f, "events_paging"),
+            Self::Markers => write!(f, "markers"),
+            Self::Search => write!(f, "search"),
+            Self::Ingest => write!(f, "ingest"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum APIError {
     DatabaseError(String),
@@ -41,3 +77,185 @@ pub struct CompressedEvent {
     pub time: crate::timing::Timing,
     pub title: String,
 }
+
+/// Body of `POST /api/events` and of each plugin's `POST /events`. A bare
+/// `{start, end}` asks for every event overlapping the range.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventsRequest {
+    #[serde(flatten)]
+    pub range: crate::timing::TimeRange,
+    /// Most events per plugin in one page; all of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub order: EventOrder,
+    /// `next` from the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl EventsRequest {
+    /// Whether this asks for one page rather than the whole range.
+    pub fn is_paged(&self) -> bool {
+        self.limit.is_some() || self.cursor.is_some()
+    }
+}
+
+impl From<crate::timing::TimeRange> for EventsRequest {
+    fn from(range: crate::timing::TimeRange) -> Self {
+        Self {
+            range,
+            limit: None,
+            order: EventOrder::default(),
+            cursor: None,
+        }
+    }
+}
+
+/// Order of events by start time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EventOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A plugin's answer to a paged `/events` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EventsPage {
+    pub events: Vec<CompressedEvent>,
+    /// Opaque cursor for the following page; `None` on the last one.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// Response of `POST /api/events`: whatever events came back, plus how
+/// each queried plugin fared, so "no events" and "plugin down" can be told
+/// apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EventsResponse {
+    pub events: HashMap<String, Vec<CompressedEvent>>,
+    pub plugins: HashMap<String, PluginStatus>,
+    /// For paged requests: cursor for the next page, while any plugin has
+    /// more events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PluginStatus {
+    pub outcome: PluginOutcome,
+    /// Time until the plugin answered (or gave up), in milliseconds.
+    pub latency_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", content = "message", rename_all = "snake_case")]
+pub enum PluginOutcome {
+    Ok,
+    Error(String),
+    Timeout,
+    /// Skipped after repeated failures; retried once its cool-down ends.
+    Degraded,
+}
+
+impl PluginOutcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+impl fmt::Display for PluginOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::Error(e) => write!(f, "{}", e),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Degraded => write!(f, "degraded, retrying later"),
+        }
+    }
+}
+
+/// Body of `POST /api/search` and of each plugin's `POST /search`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchRequest {
+    pub query: String,
+    /// Most results per plugin (default 50).
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// Response of `POST /api/search`: matches per plugin, best first, plus
+/// how each plugin fared like in [`EventsResponse`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SearchResponse {
+    pub results: HashMap<String, Vec<CompressedEvent>>,
+    pub plugins: HashMap<String, PluginStatus>,
+}
+
+/// One message on the `GET /api/live` event stream: events a plugin just
+/// stored or updated. Also the body plugins send to `/api/live/announce`
+/// (where `plugin` is ignored; the server knows who is calling).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LiveEvents {
+    #[serde(default)]
+    pub plugin: String,
+    pub events: Vec<CompressedEvent>,
+}
+
+/// One plugin's entry in `POST /api/status`, from the server's background
+/// `/health` polling.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PluginHealth {
+    pub state: HealthState,
+    /// Last time the plugin answered `/health`.
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
+    /// Round trip of the latest successful health check, in milliseconds.
+    pub latency_ms: Option<u64>,
+    /// `version` from the plugin's manifest, if it reports one.
+    pub version: Option<String>,
+    /// Why the latest health check failed.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    /// Not polled yet.
+    #[default]
+    Unknown,
+    Up,
+    Down,
+}
+
+/// Body plugins send to `POST /api/errors/report`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorReportRequest {
+    pub message: String,
+    /// `X-Request-Id` of the request the error happened in, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// One entry of `POST /api/errors`: every occurrence of the same message
+/// from the same plugin, folded together.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// The plugin the error came from, or is about.
+    pub plugin: String,
+    pub message: String,
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    pub count: u64,
+    /// `X-Request-Id` of the latest occurrence, if it happened during a
+    /// request.
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+/// Body of `POST /api/login`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginRequest {
+    pub password: String,
+}
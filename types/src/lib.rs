@@ -1,2 +1,3 @@
 pub mod api;
+pub mod request_id;
 pub mod timing;
@@ -0,0 +1,59 @@
+//! `X-Request-Id` values. The main server and the plugin SDK both keep a
+//! valid incoming id and otherwise make up one of the same shape, so ids
+//! pass unchanged between them.
+
+/// Header carrying the id, on requests and responses.
+pub const HEADER: &str = "X-Request-Id";
+/// Longest incoming id that is kept; longer ones are replaced.
+pub const MAX_LEN: usize = 128;
+
+/// Whether `id` may be passed on: 1 to [`MAX_LEN`] ASCII letters, digits
+/// and `-_.:`, so it's safe in headers, logs and query strings.
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+/// A new id from 16 random bytes: 32 lowercase hex digits.
+pub fn generate(random: [u8; 16]) -> String {
+    random.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The id for a request that came with `incoming`: kept if it
+/// [`is_valid`], otherwise a new one from `random`.
+pub fn accept_or_generate(incoming: Option<&str>, random: impl FnOnce() -> [u8; 16]) -> String {
+    match incoming {
+        Some(id) if is_valid(id) => id.to_string(),
+        _ => generate(random()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_incoming_ids_are_kept() {
+        let id = accept_or_generate(Some("0af7651916cd43dd8448eb211c80319c"), || unreachable!());
+        assert_eq!(id, "0af7651916cd43dd8448eb211c80319c");
+    }
+
+    #[test]
+    fn missing_or_invalid_ids_are_replaced() {
+        let too_long = "a".repeat(MAX_LEN + 1);
+        for incoming in [
+            None,
+            Some(""),
+            Some("a b"),
+            Some("x\r\ny"),
+            Some(too_long.as_str()),
+        ] {
+            let id = accept_or_generate(incoming, || [0xab; 16]);
+            assert_eq!(id, "ab".repeat(16), "{:?}", incoming);
+            assert!(is_valid(&id));
+        }
+    }
+}
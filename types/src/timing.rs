@@ -1,13 +1,67 @@
 use {
-    chrono::{DateTime, Local, Timelike, Utc},
+    chrono::{DateTime, Local, TimeDelta, Timelike, Utc},
     serde::{de::Visitor, Deserialize, Serialize},
-    std::{cmp::Ordering, fmt},
+    std::{cmp::Ordering, collections::HashMap, fmt},
 };
 
+/// Activity in one bucket of `POST /api/markers`.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Marker {
+    /// Start of the bucket.
     pub time: DateTime<Utc>,
+    /// Events in the bucket, over all plugins.
     pub amount: u32,
+    /// The same count per plugin.
+    #[serde(default)]
+    pub plugins: HashMap<String, u32>,
+}
+
+/// Body of `POST /api/markers`. A bare [`TimeRange`] is still accepted and
+/// buckets by hour.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MarkersRequest {
+    #[serde(flatten)]
+    pub range: TimeRange,
+    #[serde(default)]
+    pub resolution: MarkerResolution,
+}
+
+/// Most buckets one markers request may ask for.
+pub const MAX_MARKER_BUCKETS: i64 = 10_000;
+
+impl MarkersRequest {
+    /// Number of buckets the range is split into.
+    pub fn bucket_count(&self) -> i64 {
+        let step = self.resolution.step().num_milliseconds();
+        let span = (self.range.end - self.range.start).num_milliseconds();
+        (span + step - 1).div_euclid(step).max(0)
+    }
+}
+
+/// Bucket size for markers. Buckets start at the requested range's start,
+/// so a range beginning at local midnight gets local hours and days.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerResolution {
+    Minute,
+    #[serde(rename = "15min")]
+    QuarterHour,
+    #[default]
+    Hour,
+    Day,
+    Week,
+}
+
+impl MarkerResolution {
+    pub fn step(&self) -> TimeDelta {
+        match self {
+            Self::Minute => TimeDelta::minutes(1),
+            Self::QuarterHour => TimeDelta::minutes(15),
+            Self::Hour => TimeDelta::hours(1),
+            Self::Day => TimeDelta::days(1),
+            Self::Week => TimeDelta::weeks(1),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]